@@ -0,0 +1,128 @@
+use super::{ApplicationInfo, Session};
+use commands::{Clean, Command, Config, Execution, Prune, Purge, Report, Result, Update};
+use fs::{Emptiness, Path, PathList};
+use notification::LoggerNotifier;
+
+// Runs commands without command line parsing, e.g.
+// `MarkEmptyDirs::new("assets").exclude("target").update()`.
+#[derive(Debug)]
+pub struct MarkEmptyDirs {
+    config: Config,
+    dry_run: bool,
+    git: bool,
+    marker_text: String,
+    root_dirs: PathList,
+    substitute_variables: bool,
+}
+
+impl MarkEmptyDirs {
+    pub fn new<P: AsRef<Path>>(root_dir: P) -> MarkEmptyDirs {
+        MarkEmptyDirs {
+            config: Config::new(),
+            dry_run: false,
+            git: false,
+            marker_text: String::new(),
+            root_dirs: vec![root_dir.as_ref().to_owned()],
+            substitute_variables: Update::new().substitute_variables,
+        }
+    }
+
+    pub fn root_dir<P: AsRef<Path>>(mut self, dir: P) -> MarkEmptyDirs {
+        self.root_dirs.push(dir.as_ref().to_owned());
+        self
+    }
+
+    pub fn marker_name(mut self, name: &str) -> MarkEmptyDirs {
+        self.config.marker_name = name.to_owned();
+        self
+    }
+
+    pub fn marker_text(mut self, text: &str) -> MarkEmptyDirs {
+        self.marker_text = text.to_owned();
+        self
+    }
+
+    pub fn substitute_variables(mut self, substitute_variables: bool) -> MarkEmptyDirs {
+        self.substitute_variables = substitute_variables;
+        self
+    }
+
+    pub fn exclude<P: AsRef<Path>>(mut self, dir: P) -> MarkEmptyDirs {
+        self.config.exclude_dirs.push(dir.as_ref().to_owned());
+        self
+    }
+
+    pub fn dereference_symlinks(mut self, dereference_symlinks: bool) -> MarkEmptyDirs {
+        self.config.dereference_symlinks = dereference_symlinks;
+        self
+    }
+
+    pub fn emptiness(mut self, emptiness: Emptiness) -> MarkEmptyDirs {
+        self.config.emptiness = emptiness;
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> MarkEmptyDirs {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn git(mut self, git: bool) -> MarkEmptyDirs {
+        self.git = git;
+        self
+    }
+
+    // Allows setting options without a dedicated builder method.
+    pub fn configure<F: FnOnce(&mut Config)>(mut self, configure: F) -> MarkEmptyDirs {
+        configure(&mut self.config);
+        self
+    }
+
+    pub fn update(self) -> Result<Report> {
+        let mut cmd = Update::new();
+        cmd.dry_run = self.dry_run;
+        cmd.git = self.git;
+        cmd.marker_text = self.marker_text.clone();
+        cmd.root_dirs = self.root_dirs.clone();
+        cmd.substitute_variables = self.substitute_variables;
+        self.run(Box::new(cmd))
+    }
+
+    pub fn clean(self) -> Result<Report> {
+        let mut cmd = Clean::new();
+        cmd.dry_run = self.dry_run;
+        cmd.git = self.git;
+        cmd.root_dirs = self.root_dirs.clone();
+        self.run(Box::new(cmd))
+    }
+
+    pub fn prune(self) -> Result<Report> {
+        let mut cmd = Prune::new();
+        cmd.dry_run = self.dry_run;
+        cmd.root_dirs = self.root_dirs.clone();
+        self.run(Box::new(cmd))
+    }
+
+    pub fn purge(self) -> Result<Report> {
+        let mut cmd = Purge::new();
+        cmd.dry_run = self.dry_run;
+        cmd.root_dirs = self.root_dirs.clone();
+        self.run(Box::new(cmd))
+    }
+
+    fn run(self, cmd: Box<dyn Command>) -> Result<Report> {
+        let exec = if self.dry_run {
+            Execution::DryRun(cmd)
+        } else {
+            Execution::Run(cmd)
+        };
+
+        let session = Session::new(
+            ApplicationInfo::new(),
+            self.config,
+            exec,
+            LoggerNotifier::create,
+        );
+        session.run()
+    }
+}
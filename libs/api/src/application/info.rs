@@ -54,3 +54,23 @@ pub struct ApplicationInfo {
     pub vendor_name: &'static str,
     pub version_info: VersionInfo,
 }
+
+impl Default for ApplicationInfo {
+    fn default() -> ApplicationInfo {
+        ApplicationInfo::new()
+    }
+}
+
+impl ApplicationInfo {
+    pub fn new() -> ApplicationInfo {
+        ApplicationInfo {
+            copyright_year: 2018,
+            license: "Simplified BSD License",
+            name: "markemptydirs",
+            site: "https://github.com/jonnydee/markemptydirs-rs",
+            vendor_email: "jonny.dee@posteo.net",
+            vendor_name: "Johann Duscher",
+            version_info: VersionInfo::new_with_suffix(0, 1, 0, "beta1"),
+        }
+    }
+}
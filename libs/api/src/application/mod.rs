@@ -1,3 +1,6 @@
+mod builder;
+pub use self::builder::*;
+
 mod info;
 pub use self::info::*;
 
@@ -1,14 +1,20 @@
 use self::Execution::*;
 use application::ApplicationInfo;
-use commands::{Command, Config, Context, DefaultContext, Execution, Result};
+use commands;
+use commands::{Command, Config, Context, DefaultContext, Execution, Report, Result};
 use fs;
-use fs::FileSystemAccess;
-use notification::{LogLevel, MessageLength, Notifier};
+use fs::{FileSystemAccess, PathBuf};
+use notification::{BufferedNotifier, LogLevel, MessageLength, Notifier};
+use rayon;
+use std::sync::{Arc, Mutex};
+use Error;
 
 #[derive(Debug)]
 pub struct Session {
-    pub command: Box<Command>,
-    pub context: Box<Context>,
+    pub command: Box<dyn Command>,
+    pub context: Box<dyn Context>,
+    dry_run: bool,
+    plan: Option<(PathBuf, Arc<Mutex<fs::Plan>>)>,
 }
 
 impl Session {
@@ -16,7 +22,7 @@ impl Session {
         appinfo: ApplicationInfo,
         cfg: Config,
         exec: Execution,
-        nofitier_factory: impl FnOnce(LogLevel, MessageLength) -> Box<Notifier>,
+        nofitier_factory: impl FnOnce(LogLevel, MessageLength) -> Box<dyn Notifier>,
     ) -> Session {
         Session::new_with_custom_file_system_access(
             appinfo,
@@ -31,29 +37,133 @@ impl Session {
         appinfo: ApplicationInfo,
         cfg: Config,
         exec: Execution,
-        nofitier_factory: impl FnOnce(LogLevel, MessageLength) -> Box<Notifier>,
-        fsaccess_factory: impl FnOnce(bool) -> Box<FileSystemAccess>,
+        nofitier_factory: impl FnOnce(LogLevel, MessageLength) -> Box<dyn Notifier>,
+        fsaccess_factory: impl FnOnce(bool) -> Box<dyn FileSystemAccess>,
     ) -> Session {
-        let (cmd, dry_run) = match exec {
-            DryRun(cmd) => (cmd, true),
-            Run(cmd) => (cmd, false),
+        // Keep output in path order regardless of parallel execution.
+        let nofitier_factory = move |log_level, message_length| {
+            BufferedNotifier::create(nofitier_factory(log_level, message_length))
         };
 
-        let ctx = Box::new(DefaultContext::new(
-            appinfo,
-            cfg,
-            dry_run,
-            nofitier_factory,
-            fsaccess_factory,
-        ));
+        let (cmd, dry_run, plan) = match exec {
+            DryRun(cmd) => (cmd, true, None),
+            Plan(cmd, plan_file) => (cmd, true, Some((plan_file, Arc::default()))),
+            Run(cmd) => (cmd, false, None),
+        };
+
+        let mut ctx = match plan {
+            Some((_, ref plan)) => {
+                let plan = Arc::clone(plan);
+                DefaultContext::new(appinfo, cfg, dry_run, nofitier_factory, move |_| {
+                    Box::new(fs::PlanningFileSystemAccess::new(plan)) as Box<dyn FileSystemAccess>
+                })
+            }
+            None => DefaultContext::new(appinfo, cfg, dry_run, nofitier_factory, fsaccess_factory),
+        };
+        ctx.set_read_all_marker_backends(cmd.reads_all_marker_backends());
 
         Session {
             command: cmd,
-            context: ctx,
+            context: Box::new(ctx),
+            dry_run,
+            plan,
+        }
+    }
+
+    pub fn run(&self) -> Result<Report> {
+        let notifier = self.context.get_notifier();
+        let start = std::time::Instant::now();
+        if let Err(error) = self.context.get_config().validate() {
+            notifier.error("config", "Invalid configuration", &error.to_string(), None);
+            notifier.flush();
+            return Err(Error::Message(
+                "Marker and manifest names must be plain file names",
+            ));
+        }
+        for var in &self.context.get_config().applied_env_vars {
+            notifier.info("config", "Environment variable applied", var, None);
+        }
+        if self.context.get_config().low_io_priority {
+            // Before any worker threads start, so that they inherit the priority.
+            if let Err(error) = fs::set_low_io_priority() {
+                notifier.warn(
+                    "config",
+                    "Lowering I/O priority failed",
+                    &error.to_string(),
+                    None,
+                );
+            }
+        }
+        notifier.flush();
+        let _locks = self.lock_root_dirs()?;
+        let result = self.execute();
+        notifier.flush();
+        let mut report = result?;
+        report.dry_run = self.dry_run;
+        report.retries = self.context.take_retry_records();
+        report.retries.sort_by(|a, b| a.path.cmp(&b.path));
+        for record in report.retries.iter().filter(|record| !record.succeeded) {
+            notifier.error(
+                "retry",
+                "Giving up after retries",
+                &format!(
+                    "{} ({} failed {} times: {})",
+                    fs::to_native(&record.path),
+                    record.operation,
+                    record.retries + 1,
+                    record.error
+                ),
+                None,
+            );
+        }
+        notifier.flush();
+        report.timings.total = start.elapsed();
+
+        if let Some((ref plan_file, ref plan)) = self.plan {
+            let mut plan = plan.lock().unwrap();
+            // Applying the plan protects the same root directories as running the command.
+            plan.root_dirs = match self.command.get_root_dirs() {
+                Some(root_dirs) => root_dirs
+                    .iter()
+                    .map(|root_dir| {
+                        root_dir
+                            .canonicalize()
+                            .or_else(|_| fs::get_absolute_dir(root_dir))
+                    })
+                    .collect::<std::io::Result<_>>()
+                    .map_err(Error::Io)?,
+                None => vec![],
+            };
+            plan.write(plan_file).map_err(Error::Io)?;
+            notifier.info(
+                "write_plan",
+                "Plan written",
+                &fs::to_native(plan_file),
+                None,
+            );
+            notifier.flush();
         }
+        Ok(report)
     }
 
-    pub fn run(&self) -> Result<()> {
-        self.command.execute(&*self.context)
+    // Dry runs do not modify anything and hence need no lock.
+    fn lock_root_dirs(&self) -> Result<Vec<fs::RootLock>> {
+        match self.command.get_root_dirs() {
+            Some(root_dirs) if self.context.get_config().lock_root_dirs && !self.dry_run => {
+                commands::lock_root_dirs(&*self.context, root_dirs)
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    fn execute(&self) -> Result<Report> {
+        match self.context.get_config().jobs {
+            Some(jobs) => rayon::ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build()
+                .map_err(|error| Error::Other(Box::new(error)))?
+                .install(|| self.command.execute(&*self.context)),
+            None => self.command.execute(&*self.context),
+        }
     }
 }
@@ -0,0 +1,107 @@
+use super::*;
+
+use fs;
+use fs::Plan;
+
+#[derive(PartialEq, Debug)]
+pub struct Apply {
+    pub dry_run: bool,
+    pub plan_file: PathBuf,
+}
+
+impl Default for Apply {
+    fn default() -> Apply {
+        Apply::new()
+    }
+}
+
+impl Apply {
+    pub fn new() -> Apply {
+        Apply {
+            dry_run: false,
+            plan_file: PathBuf::new(),
+        }
+    }
+}
+
+impl Command for Apply {
+    fn execute(&self, ctx: &dyn Context) -> Result<Report> {
+        let mut report = Report::new();
+        let notifier = ctx.get_notifier();
+        let plan = Plan::read(&self.plan_file).map_err(Error::Io)?;
+
+        // Protect the root directories just like the command planned.
+        if plan.root_dirs.is_empty() {
+            notifier.error(
+                "apply",
+                "Plan without root directories",
+                &fs::to_native(&self.plan_file),
+                None,
+            );
+            return Err(Error::Message(
+                "Plan without root directories; nothing applied",
+            ));
+        }
+        check_root_dirs(ctx, &plan.root_dirs)?;
+        let _locks = if ctx.get_config().lock_root_dirs && !self.dry_run {
+            lock_root_dirs(ctx, &plan.root_dirs)?
+        } else {
+            vec![]
+        };
+
+        // Verify all steps before the first change, so that a drifted or edited plan is never
+        // applied halfway. All preconditions were captured against the same tree.
+        let mut rejected = false;
+        for step in &plan.steps {
+            let path = step.operation.get_path();
+            let info = match step.operation.get_modified_path() {
+                Some(modified_path)
+                    if fs::check_inside_root_dirs(&plan.root_dirs, modified_path).is_err() =>
+                {
+                    "Path outside of root directories"
+                }
+                _ if !step.precondition.is_met(path) => "Precondition not met",
+                _ => continue,
+            };
+            notifier.error("apply", info, &fs::to_native(path), None);
+            rejected = true;
+        }
+        if rejected {
+            return Err(Error::Message(
+                "Plan does not match the directory tree; nothing applied",
+            ));
+        }
+
+        // Stop at the first failure.
+        let fsaccess = ctx.get_file_system_access();
+        let mut steps = plan.steps.iter();
+        for step in &mut steps {
+            let path = step.operation.get_path();
+            let result = step.operation.apply(fsaccess);
+            report.record(Action::ApplyOperation, path, Outcome::from_result(&result));
+            match result {
+                Ok(()) => notifier.info("apply", "Operation applied", &fs::to_native(path), None),
+                Err(error) => {
+                    notifier.error(
+                        "apply",
+                        "Operation failed",
+                        &fs::to_native(path),
+                        Some(Error::Io(error)),
+                    );
+                    break;
+                }
+            }
+        }
+        for step in steps {
+            let path = step.operation.get_path();
+            notifier.warn("apply", "Operation skipped", &fs::to_native(path), None);
+            report.record(
+                Action::ApplyOperation,
+                path,
+                Outcome::Skipped("earlier operation failed".to_owned()),
+            );
+        }
+
+        Ok(report)
+    }
+}
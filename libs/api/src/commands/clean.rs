@@ -1,35 +1,136 @@
 use super::*;
 
+use fs::{DeletePolicy, PurgePolicy};
 use rayon::prelude::*;
+use std::collections::HashSet;
+use std::sync::Mutex;
 
 #[derive(PartialEq, Debug)]
 pub struct Clean {
+    pub confirmation: Confirmation,
     pub delete_hook: String,
     pub dry_run: bool,
+    pub git: bool,
     pub root_dirs: PathList,
+    pub strict: bool,
+}
+
+impl Default for Clean {
+    fn default() -> Clean {
+        Clean::new()
+    }
 }
 
 impl Clean {
     pub fn new() -> Clean {
         Clean {
+            confirmation: Confirmation::Never,
             delete_hook: String::new(),
             dry_run: false,
+            git: false,
             root_dirs: Config::default_root_dirs(),
+            strict: false,
         }
     }
 }
 
 impl Command for Clean {
-    fn execute(&self, ctx: &Context) -> Result<()> {
-        let descr_list = ctx.crawl_dirs(&self.root_dirs);
+    fn execute(&self, ctx: &dyn Context) -> Result<Report> {
+        check_root_dirs(ctx, &self.root_dirs)?;
+
+        let mut report = Report::new();
+        let descr_list = crawl_dirs(ctx, &self.root_dirs, &mut report);
+        let report = Mutex::new(report);
+
+        let protected_dirs: PathList = descr_list
+            .iter()
+            .filter(|descr| {
+                descr.has_marker() && descr.get_directives().purge == PurgePolicy::Never
+            })
+            .map(|descr| descr.dir.clone())
+            .collect();
+
+        // In strict mode, files merely named like markers are left alone.
+        let is_trusted = |descr: &DirDescriptor| !self.strict || is_signed_marker(ctx, descr);
+        for descr in &descr_list {
+            if descr.has_marker() && !is_trusted(descr) {
+                let outcome = ignore_unsigned_marker(ctx, descr);
+                report
+                    .lock()
+                    .unwrap()
+                    .record(Action::DeleteMarker, &descr.dir, outcome);
+            }
+        }
 
-        // Delete all markers.
+        let mut planned_dirs: Vec<_> = descr_list
+            .iter()
+            .filter(|descr| descr.has_marker() && is_trusted(descr))
+            .map(|descr| PlannedDir {
+                dir: descr.dir.clone(),
+                // Directories recorded in a manifest have no marker file.
+                file_count: descr.get_marker_direntry().map_or(0, |_| 1),
+                size: descr
+                    .get_marker_direntry()
+                    .and_then(|entry| entry.metadata().ok())
+                    .map_or(0, |md| md.len()),
+            })
+            .collect();
+        planned_dirs.sort_by(|a, b| a.dir.cmp(&b.dir));
+
+        let selected_dirs: HashSet<_> = confirm_planned_dirs(
+            ctx,
+            "Clean",
+            &self.root_dirs,
+            planned_dirs,
+            self.confirmation,
+        )?
+        .into_iter()
+        .collect();
+
+        // Delete all confirmed markers, purging directories asking for it beforehand.
         descr_list.par_iter().for_each(|descr| {
-            if descr.has_marker() {
-                ctx.delete_marker(&descr.dir);
+            if !descr.has_marker() || !selected_dirs.contains(&descr.dir) {
+                return;
             }
+            let directives = descr.get_directives();
+            if directives.purge == PurgePolicy::OnClean {
+                for (child, is_dir) in get_purgeable_children(descr, &protected_dirs) {
+                    let (action, outcome) = if is_dir {
+                        (Action::DeleteChildDir, ctx.delete_child_dir(&child))
+                    } else {
+                        (Action::DeleteChildFile, ctx.delete_child_file(&child))
+                    };
+                    report.lock().unwrap().record(action, &child, outcome);
+                }
+            }
+            let outcome = if directives.delete == DeletePolicy::Never {
+                keep_marker(ctx, descr)
+            } else {
+                ctx.delete_marker(descr, None)
+            };
+            report
+                .lock()
+                .unwrap()
+                .record(Action::DeleteMarker, &descr.dir, outcome);
         });
 
-        Ok(())
+        let mut report = report.into_inner().unwrap();
+        write_manifests(ctx, &mut report);
+
+        // Unstage deleted markers.
+        if self.git {
+            ctx.update_git_index(
+                &self.root_dirs,
+                &PathList::new(),
+                &report.get_done_paths(Action::DeleteMarker),
+            );
+        }
+
+        report.sort();
+        Ok(report)
+    }
+
+    fn get_root_dirs(&self) -> Option<&PathList> {
+        Some(&self.root_dirs)
     }
 }
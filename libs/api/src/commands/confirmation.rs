@@ -0,0 +1,139 @@
+use super::*;
+
+use fs;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Confirmation {
+    Never,
+    Once,
+    PerDirectory,
+}
+
+#[derive(Debug)]
+pub struct PlannedDir {
+    pub dir: PathBuf,
+    pub file_count: usize,
+    pub size: u64,
+}
+
+pub fn confirm_planned_dirs(
+    ctx: &dyn Context,
+    action: &str,
+    root_dirs: &PathList,
+    planned_dirs: Vec<PlannedDir>,
+    confirmation: Confirmation,
+) -> Result<PathList> {
+    if confirmation == Confirmation::Never || planned_dirs.is_empty() {
+        return Ok(planned_dirs
+            .into_iter()
+            .map(|planned| planned.dir)
+            .collect());
+    }
+
+    let prompter = ctx.get_prompter();
+    if !prompter.is_interactive() {
+        ctx.get_notifier().error(
+            "confirm",
+            "Confirmation impossible without terminal",
+            action,
+            None,
+        );
+        return Err(Error::Message(
+            "Refusing to proceed without confirmation; use --yes to do so anyway",
+        ));
+    }
+
+    // Show pending messages before asking.
+    ctx.get_notifier().flush();
+    print_plan(ctx, action, root_dirs, &planned_dirs);
+
+    if confirmation == Confirmation::Once {
+        let question = format!("{} {} directories", action, planned_dirs.len());
+        match prompter.ask(&question, &["all", "none", "each"], 1) {
+            0 => {
+                return Ok(planned_dirs
+                    .into_iter()
+                    .map(|planned| planned.dir)
+                    .collect())
+            }
+            1 => return Ok(vec![]),
+            _ => (),
+        }
+    }
+
+    let mut selected_dirs = vec![];
+    let mut planned_dirs = planned_dirs.into_iter();
+    while let Some(planned) = planned_dirs.next() {
+        let question = format!(
+            "{} {} ({})",
+            action,
+            get_display_dir(&planned.dir),
+            format_usage(planned.file_count, planned.size)
+        );
+        match prompter.ask(&question, &["yes", "no", "all", "quit"], 1) {
+            0 => selected_dirs.push(planned.dir),
+            1 => (),
+            2 => {
+                selected_dirs.push(planned.dir);
+                selected_dirs.extend(planned_dirs.map(|planned| planned.dir));
+                break;
+            }
+            _ => break,
+        }
+    }
+    Ok(selected_dirs)
+}
+
+fn print_plan(ctx: &dyn Context, action: &str, root_dirs: &PathList, planned_dirs: &[PlannedDir]) {
+    for root_dir in root_dirs {
+        let group: Vec<_> = planned_dirs
+            .iter()
+            .filter(|planned| match ctx.get_root_dir(&planned.dir, root_dirs) {
+                Ok(Some(dir)) => dir == root_dir,
+                _ => false,
+            })
+            .collect();
+        if group.is_empty() {
+            continue;
+        }
+
+        eprintln!("{} in {}:", action, fs::to_native(root_dir));
+        for planned in &group {
+            eprintln!(
+                "    {} ({})",
+                get_display_dir(&planned.dir),
+                format_usage(planned.file_count, planned.size)
+            );
+        }
+        eprintln!(
+            "    Total: {} directories, {}",
+            group.len(),
+            format_usage(
+                group.iter().map(|planned| planned.file_count).sum(),
+                group.iter().map(|planned| planned.size).sum()
+            )
+        );
+    }
+}
+
+fn get_display_dir(dir: &PathBuf) -> String {
+    match fs::get_relative_dir_to_current_dir(dir) {
+        Ok(Some(dir)) => fs::to_native(&dir),
+        _ => fs::to_native(dir),
+    }
+}
+
+fn format_usage(file_count: usize, size: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < units.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} files, {} {}", file_count, size, units[unit])
+    } else {
+        format!("{} files, {:.1} {}", file_count, size, units[unit])
+    }
+}
@@ -1,19 +1,51 @@
-use super::{Error, Path, PathBuf, PathList};
+use super::{DirStatus, Error, Outcome, Path, PathBuf, PathList};
 use crate::fs;
-use crate::fs::{DirDescriptorList, FileSystemAccess, FileSystemCrawler};
+use crate::fs::{
+    DirDescriptor, DirDescriptorList, Emptiness, FileOptions, FileSystemAccess, FileSystemCrawler,
+    Manifest, MarkerStrategy, RetryLog, RetryPolicy, RetryRecord, RetryingFileSystemAccess,
+    Throttle, ThrottlePolicy, ThrottlingFileSystemAccess, Trash, TrashLocation, MARKER_XATTR_NAME,
+};
 use application::ApplicationInfo;
+use git;
+use git::{GitAccess, GitRepository};
+use interaction::{Prompter, StdinPrompter};
 use notification::{LogLevel, MessageLength, Notifier};
 use std;
+use std::collections::HashSet;
 use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
 #[derive(PartialEq, Debug)]
 pub struct Config {
+    pub applied_env_vars: Vec<String>,
+    pub emptiness: Emptiness,
     pub exclude_dirs: PathList,
     pub executable_file: PathBuf,
+    pub force: bool,
+    pub inherit_marker_owner: bool,
+    pub jobs: Option<usize>,
+    pub json_report: bool,
+    pub lock_root_dirs: bool,
+    pub lock_timeout: Duration,
     pub log_level: LogLevel,
     pub message_length: MessageLength,
+    pub manifest_name: String,
+    pub marker_mode: Option<u32>,
+    pub marker_mtime: Option<SystemTime>,
     pub marker_name: String,
+    pub marker_strategy: MarkerStrategy,
     pub dereference_symlinks: bool,
+    pub low_io_priority: bool,
+    pub preserve_dir_times: bool,
+    pub reproducible: bool,
+    pub retry_policy: RetryPolicy,
+    pub selected_paths: Option<PathList>,
+    pub select_parents: bool,
+    pub sign_markers: bool,
+    pub source_date_epoch: Option<SystemTime>,
+    pub throttle_policy: ThrottlePolicy,
+    pub trash: Option<TrashLocation>,
 }
 
 impl Config {
@@ -23,14 +55,42 @@ impl Config {
 
     pub fn new() -> Config {
         Config {
+            applied_env_vars: vec![],
+            emptiness: Emptiness::FileSystem,
             exclude_dirs: vec![Path::new(".git").to_owned()],
             executable_file: PathBuf::new(),
+            force: false,
+            inherit_marker_owner: false,
+            jobs: None,
+            json_report: false,
+            lock_root_dirs: true,
+            lock_timeout: Duration::from_secs(0),
             log_level: LogLevel::Error,
             message_length: MessageLength::Long,
+            manifest_name: ".emptydirs".to_string(),
+            marker_mode: None,
+            marker_mtime: None,
             marker_name: ".emptydir".to_string(),
+            marker_strategy: MarkerStrategy::File,
             dereference_symlinks: false,
+            low_io_priority: false,
+            preserve_dir_times: false,
+            reproducible: false,
+            retry_policy: RetryPolicy::new(),
+            selected_paths: None,
+            select_parents: false,
+            sign_markers: true,
+            source_date_epoch: None,
+            throttle_policy: ThrottlePolicy::new(),
+            trash: None,
         }
     }
+
+    // Names must be plain file names, so that markers and manifests stay in their directory.
+    pub fn validate(&self) -> std::io::Result<()> {
+        fs::validate_file_name(&self.marker_name)?;
+        fs::validate_file_name(&self.manifest_name)
+    }
 }
 
 pub trait Context: Sync + Debug {
@@ -38,8 +98,12 @@ pub trait Context: Sync + Debug {
 
     fn get_config(&self) -> &Config;
 
+    fn get_file_system_access(&self) -> &dyn FileSystemAccess;
+
     fn get_notifier(&self) -> &Notifier;
 
+    fn get_prompter(&self) -> &dyn Prompter;
+
     fn get_root_dir<'a>(
         &self,
         dir: &PathBuf,
@@ -48,21 +112,51 @@ pub trait Context: Sync + Debug {
 
     fn crawl_dirs(&self, root_dirs: &PathList) -> DirDescriptorList;
 
-    fn create_marker(&self, dir: &PathBuf, text: &String);
+    fn crawl_dirs_shallow(&self, dirs: &PathList) -> DirDescriptorList;
+
+    fn convert_marker(&self, dir: &PathBuf, to: MarkerStrategy) -> Outcome;
+
+    // Mutations of crawled directories re-check the status they rely on, since directories
+    // may change after crawling.
+    fn create_marker(&self, descr: &DirDescriptor, text: &String) -> Outcome;
+
+    fn delete_child_file(&self, file: &PathBuf) -> Outcome;
+
+    fn delete_child_dir(&self, dir: &PathBuf) -> Outcome;
+
+    fn delete_empty_dir(&self, dir: &PathBuf) -> Outcome;
+
+    fn delete_marker(&self, descr: &DirDescriptor, expected: Option<DirStatus>) -> Outcome;
 
-    fn delete_child_file(&self, file: &PathBuf);
+    // Writes manifests changed by marker creation or deletion.
+    fn write_manifests(&self) -> Vec<(PathBuf, Outcome)>;
 
-    fn delete_child_dir(&self, dir: &PathBuf);
+    // Stages the markers of the directories marked and unstages those of the unmarked ones.
+    fn update_git_index(
+        &self,
+        root_dirs: &PathList,
+        marked_dirs: &PathList,
+        unmarked_dirs: &PathList,
+    );
 
-    fn delete_marker(&self, dir: &PathBuf);
+    // Returns the operations retried since the last call.
+    fn take_retry_records(&self) -> Vec<RetryRecord>;
 }
 
 #[derive(Debug)]
 pub struct DefaultContext {
     appinfo: ApplicationInfo,
     config: Config,
-    fsaccess: Box<FileSystemAccess>,
-    notifier: Box<Notifier>,
+    fsaccess: Box<dyn FileSystemAccess>,
+    gitaccess: Box<dyn GitAccess>,
+    manifests: Mutex<Vec<Manifest>>,
+    notifier: Box<dyn Notifier>,
+    prompter: Box<dyn Prompter>,
+    read_all_marker_backends: bool,
+    read_throttle: Arc<Throttle>,
+    retry_log: Arc<RetryLog>,
+    root_dirs: Mutex<PathList>,
+    trash: Option<Trash>,
 }
 
 impl DefaultContext {
@@ -73,32 +167,151 @@ impl DefaultContext {
         notifier_factory: impl FnOnce(LogLevel, MessageLength) -> Box<Notifier>,
         fsaccess_factory: impl FnOnce(bool) -> Box<FileSystemAccess>,
     ) -> DefaultContext {
+        let retry_log = Arc::new(RetryLog::default());
         DefaultContext {
-            appinfo: appinfo,
+            appinfo,
             notifier: notifier_factory(config.log_level, config.message_length),
-            fsaccess: fsaccess_factory(dry_run),
-            config: config,
+            // Throttle inside of retrying, so that each attempt counts as an operation.
+            fsaccess: Box::new(RetryingFileSystemAccess::new(
+                Box::new(ThrottlingFileSystemAccess::new(
+                    fsaccess_factory(dry_run),
+                    config.throttle_policy.clone(),
+                )),
+                config.retry_policy.clone(),
+                Arc::clone(&retry_log),
+            )),
+            gitaccess: git::create_git_access(dry_run),
+            manifests: Mutex::new(vec![]),
+            prompter: StdinPrompter::create(),
+            read_all_marker_backends: false,
+            read_throttle: Arc::new(Throttle::new(config.throttle_policy.clone())),
+            retry_log,
+            root_dirs: Mutex::new(vec![]),
+            trash: config.trash.clone().map(Trash::new),
+            config,
         }
     }
 
+    // Makes crawling find markers of other backends than the configured one as well.
+    pub fn set_read_all_marker_backends(&mut self, read_all_marker_backends: bool) {
+        self.read_all_marker_backends = read_all_marker_backends;
+    }
+
     fn create_marker_impl(&self, dir: &PathBuf, text: &String) -> std::io::Result<()> {
-        let ref marker_file_path = self.get_marker_file_path(dir)?;
+        self.check_inside_root_dirs(dir)?;
+
+        // Sign markers, so that they can be told apart from files of the same name.
+        let text = &(if self.config.sign_markers {
+            fs::sign_marker_text(
+                text,
+                &format!("{}/{}", self.appinfo.name, self.appinfo.version_info),
+            )
+        } else {
+            text.clone()
+        });
+        match self.config.marker_strategy {
+            MarkerStrategy::File => {
+                let marker_file_path = &self.create_marker_file(dir, text)?;
+                self.notifier.info(
+                    "create_marker",
+                    "Marker created",
+                    &fs::to_native(marker_file_path),
+                    None,
+                );
+            }
+            MarkerStrategy::Manifest => {
+                self.update_manifest(dir, true)?;
+                self.notifier.info(
+                    "create_marker",
+                    "Directory recorded in manifest",
+                    &fs::to_native(dir),
+                    None,
+                );
+            }
+            MarkerStrategy::Xattr => {
+                // Setting an attribute replaces its value, so check for it beforehand.
+                if fs::get_xattr(dir, MARKER_XATTR_NAME)?.is_some() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::AlreadyExists,
+                        "marker attribute already set",
+                    ));
+                }
+                self.fsaccess.set_xattr(dir, MARKER_XATTR_NAME, text)?;
+                self.notifier.info(
+                    "create_marker",
+                    "Marker attribute set",
+                    &fs::to_native(dir),
+                    None,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn create_marker_file(&self, dir: &PathBuf, text: &str) -> std::io::Result<PathBuf> {
+        let marker_file_path = self.get_marker_file(dir)?;
+
+        let options = FileOptions {
+            mode: self.config.marker_mode,
+            mtime: self.config.marker_mtime,
+            owner: if self.config.inherit_marker_owner {
+                fs::get_owner(dir)?
+            } else {
+                None
+            },
+        };
 
         // Write marker to disk.
-        self.fsaccess.create_file(marker_file_path, text)?;
+        self.preserving_dir_times(dir, || {
+            self.fsaccess.create_file(&marker_file_path, text, &options)
+        })?;
+        Ok(marker_file_path)
+    }
+
+    fn convert_marker_impl(&self, dir: &PathBuf, to: MarkerStrategy) -> std::io::Result<()> {
+        self.check_inside_root_dirs(dir)?;
+        let marker_file_path = &self.get_marker_file(dir)?;
+        match to {
+            MarkerStrategy::File => {
+                // Keep an existing marker file rather than the attribute's text.
+                if !marker_file_path.is_file() {
+                    let text = fs::get_xattr(dir, MARKER_XATTR_NAME)?.unwrap_or_default();
+                    self.create_marker_file(dir, &String::from_utf8_lossy(&text))?;
+                }
+                self.fsaccess.remove_xattr(dir, MARKER_XATTR_NAME)?;
+            }
+            MarkerStrategy::Xattr => {
+                if fs::get_xattr(dir, MARKER_XATTR_NAME)?.is_none() {
+                    let text = std::fs::read_to_string(marker_file_path)?;
+                    self.fsaccess.set_xattr(dir, MARKER_XATTR_NAME, &text)?;
+                }
+                self.preserving_dir_times(dir, || self.fsaccess.remove_file(marker_file_path))?;
+            }
+            MarkerStrategy::Manifest => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "markers cannot be converted into manifest entries",
+                ))
+            }
+        }
 
         self.notifier.info(
-            "create_marker",
-            "Marker created",
-            &fs::to_native(marker_file_path),
+            "convert_marker",
+            "Marker converted",
+            &fs::to_native(dir),
             None,
         );
         Ok(())
     }
 
     fn delete_child_file_impl(&self, file: &PathBuf) -> std::io::Result<()> {
-        // Remove file from disk.
-        self.fsaccess.remove_file(file)?;
+        self.check_inside_root_dirs(file)?;
+
+        // Remove file from disk or move it to trash.
+        match self.trash {
+            Some(ref trash) => trash.put(&*self.fsaccess, file)?,
+            None => self.fsaccess.remove_file(file)?,
+        }
 
         self.notifier.info(
             "delete_child_file",
@@ -110,8 +323,13 @@ impl DefaultContext {
     }
 
     fn delete_child_dir_impl(&self, dir: &PathBuf) -> std::io::Result<()> {
-        // Remove dir from disk.
-        self.fsaccess.remove_dir_all(dir)?;
+        self.check_inside_root_dirs(dir)?;
+
+        // Remove dir from disk or move it to trash.
+        match self.trash {
+            Some(ref trash) => trash.put(&*self.fsaccess, dir)?,
+            None => self.fsaccess.remove_dir_all(dir)?,
+        }
 
         self.notifier.info(
             "delete_child_dir",
@@ -122,26 +340,369 @@ impl DefaultContext {
         Ok(())
     }
 
-    fn delete_marker_impl(&self, dir: &PathBuf) -> std::io::Result<()> {
-        let ref marker_file_path = self.get_marker_file_path(dir)?;
+    fn delete_empty_dir_impl(&self, dir: &PathBuf) -> std::io::Result<()> {
+        self.check_inside_root_dirs(dir)?;
 
-        // Remove marker from disk.
-        self.fsaccess.remove_file(marker_file_path)?;
+        // Remove dir from disk, failing if it is not empty after all.
+        match dir.parent() {
+            Some(parent) => self.preserving_dir_times(parent, || self.fsaccess.remove_dir(dir))?,
+            None => self.fsaccess.remove_dir(dir)?,
+        }
 
         self.notifier.info(
-            "delete_marker",
-            "Marker deleted",
-            &fs::to_native(marker_file_path),
+            "delete_empty_dir",
+            "Empty dir deleted",
+            &fs::to_native(dir),
             None,
         );
         Ok(())
     }
 
-    fn get_marker_file_path(&self, dir: &PathBuf) -> std::io::Result<PathBuf> {
+    fn delete_marker_impl(&self, dir: &PathBuf) -> std::io::Result<()> {
+        self.check_inside_root_dirs(dir)?;
+
+        if self.config.marker_strategy == MarkerStrategy::Manifest {
+            self.update_manifest(dir, false)?;
+            self.notifier.info(
+                "delete_marker",
+                "Directory removed from manifest",
+                &fs::to_native(dir),
+                None,
+            );
+            return Ok(());
+        }
+
+        // Delete markers of both kinds, since a conversion may have left both.
+        let marker_file_path = &self.get_marker_file(dir)?;
+        let has_marker_file = marker_file_path.is_file();
+        let has_marker_xattr = fs::get_xattr(dir, MARKER_XATTR_NAME)?.is_some();
+        if !has_marker_file && !has_marker_xattr {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "marker not found",
+            ));
+        }
+
+        if has_marker_file {
+            // Remove marker from disk.
+            self.preserving_dir_times(dir, || self.fsaccess.remove_file(marker_file_path))?;
+            self.notifier.info(
+                "delete_marker",
+                "Marker deleted",
+                &fs::to_native(marker_file_path),
+                None,
+            );
+        }
+
+        if has_marker_xattr {
+            self.fsaccess.remove_xattr(dir, MARKER_XATTR_NAME)?;
+            self.notifier.info(
+                "delete_marker",
+                "Marker attribute removed",
+                &fs::to_native(dir),
+                None,
+            );
+        }
+        Ok(())
+    }
+
+    fn update_manifest(&self, dir: &Path, listed: bool) -> std::io::Result<()> {
+        use std::io::{Error, ErrorKind};
+
+        let dir = dir.canonicalize()?;
+        let mut manifests = self.manifests.lock().unwrap();
+        let index = fs::find_manifest(&manifests, &dir)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "no manifest for directory"))?;
+        let manifest = &mut manifests[index];
+        if listed {
+            if !manifest.insert(&dir)? {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    "directory already listed",
+                ));
+            }
+        } else if !manifest.remove(&dir)? {
+            return Err(Error::new(ErrorKind::NotFound, "directory not listed"));
+        }
+        Ok(())
+    }
+
+    // Reads the manifests of the given root directories, or in case no root directory is
+    // known, the manifest found in the nearest ancestor directory.
+    fn read_manifests(&self, dirs: &PathList, are_root_dirs: bool) {
+        if self.config.marker_strategy != MarkerStrategy::Manifest {
+            return;
+        }
+
+        let mut manifests = self.manifests.lock().unwrap();
+        for dir in dirs {
+            let dir = match dir.canonicalize() {
+                Ok(dir) => dir,
+                Err(_) => continue,
+            };
+            let root_dir = if are_root_dirs {
+                if manifests.iter().any(|manifest| manifest.root_dir == dir) {
+                    continue;
+                }
+                dir
+            } else {
+                if manifests.iter().any(|manifest| manifest.covers(&dir)) {
+                    continue;
+                }
+                match dir
+                    .ancestors()
+                    .find(|dir| dir.join(&self.config.manifest_name).is_file())
+                {
+                    Some(root_dir) => root_dir.to_owned(),
+                    None => continue,
+                }
+            };
+            match Manifest::read(&root_dir, &self.config.manifest_name) {
+                Ok(manifest) => manifests.push(manifest),
+                Err(error) => self.notifier.error(
+                    "read_manifest",
+                    "Manifest reading failed",
+                    &fs::to_native(&root_dir.join(&self.config.manifest_name)),
+                    Some(Error::Io(error)),
+                ),
+            }
+        }
+    }
+
+    fn discover_git_repositories(&self, root_dirs: &PathList) -> Vec<GitRepository> {
+        let mut repos: Vec<GitRepository> = vec![];
+        for root_dir in root_dirs {
+            let repo = root_dir
+                .canonicalize()
+                .and_then(|root_dir| GitRepository::discover(&root_dir));
+            match repo {
+                Ok(repo) => {
+                    if !repos.contains(&repo) {
+                        repos.push(repo);
+                    }
+                }
+                Err(error) => self.notifier.error(
+                    "update_git_index",
+                    "Git repository lookup failed",
+                    &fs::to_native(root_dir),
+                    Some(Error::Io(error)),
+                ),
+            }
+        }
+        repos
+    }
+
+    fn update_git_index_impl(
+        &self,
+        repo: &GitRepository,
+        staged: &PathList,
+        unstaged: &PathList,
+    ) -> std::io::Result<()> {
+        self.gitaccess.stage(repo, staged)?;
+        for marker_file in staged {
+            self.notifier.info(
+                "update_git_index",
+                "Marker staged",
+                &fs::to_native(marker_file),
+                None,
+            );
+        }
+
+        self.gitaccess.unstage(repo, unstaged)?;
+        for marker_file in unstaged {
+            self.notifier.info(
+                "update_git_index",
+                "Marker unstaged",
+                &fs::to_native(marker_file),
+                None,
+            );
+        }
+
+        let marker_files: PathList = staged.iter().chain(unstaged).cloned().collect();
+        for line in repo.status(&marker_files)? {
+            self.notifier
+                .info("update_git_index", "Index changed", &line, None);
+        }
+        Ok(())
+    }
+
+    fn create_crawler(&self) -> FileSystemCrawler {
+        let manifest_dirs: HashSet<_> = self
+            .manifests
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|manifest| manifest.get_dirs())
+            .collect();
+        FileSystemCrawler {
+            exclude_dirs: self.config.exclude_dirs.clone(),
+            dereference_symlinks: self.config.dereference_symlinks,
+            emptiness: self.config.emptiness,
+            manifest_dirs,
+            manifest_name: self.config.manifest_name.clone(),
+            marker_name: self.config.marker_name.clone(),
+            marker_strategy: self.config.marker_strategy,
+            read_marker_xattrs: match self.config.marker_strategy {
+                MarkerStrategy::File => self.read_all_marker_backends,
+                MarkerStrategy::Manifest => false,
+                MarkerStrategy::Xattr => true,
+            },
+            read_throttle: Arc::clone(&self.read_throttle),
+            retry_log: Arc::clone(&self.retry_log),
+            retry_policy: self.config.retry_policy.clone(),
+        }
+    }
+
+    fn select_dirs(&self, root_dirs: &PathList, paths: &PathList) -> PathList {
+        let root_dirs: PathList = root_dirs
+            .iter()
+            .filter_map(|root_dir| root_dir.canonicalize().ok())
+            .collect();
+
+        let mut dirs = std::collections::BTreeSet::new();
+        for path in paths {
+            // Listed files and deleted paths select their parent directory.
+            let dir = match fs::get_absolute_dir(path) {
+                Ok(ref path) if path.is_dir() => Some(path.to_owned()),
+                Ok(ref path) => path.parent().map(|parent| parent.to_owned()),
+                Err(_) => None,
+            };
+            let dir = match dir.map(|dir| dir.canonicalize()) {
+                Some(Ok(dir)) => dir,
+                _ => continue,
+            };
+
+            let root_dir = match root_dirs.iter().find(|root_dir| dir.starts_with(root_dir)) {
+                Some(root_dir) => root_dir,
+                None => {
+                    self.notifier.warn(
+                        "select_dirs",
+                        "Path outside of root directories ignored",
+                        &fs::to_native(path),
+                        None,
+                    );
+                    continue;
+                }
+            };
+
+            if self.config.select_parents {
+                dirs.extend(
+                    dir.ancestors()
+                        .take_while(|parent| parent.starts_with(root_dir))
+                        .map(|parent| parent.to_owned()),
+                );
+            } else {
+                dirs.insert(dir);
+            }
+        }
+        dirs.into_iter().collect()
+    }
+
+    // Remembers crawled directories, which are the only ones to be modified.
+    fn add_root_dirs(&self, dirs: &PathList) {
+        let mut root_dirs = self.root_dirs.lock().unwrap();
+        for dir in dirs {
+            if let Ok(dir) = dir.canonicalize() {
+                if !root_dirs.contains(&dir) {
+                    root_dirs.push(dir);
+                }
+            }
+        }
+    }
+
+    // Directories reached by following symlinks are crawled by their canonical path, so
+    // ones outside of the root directories are refused as well.
+    fn check_inside_root_dirs(&self, path: &Path) -> std::io::Result<()> {
+        fs::check_inside_root_dirs(&self.root_dirs.lock().unwrap(), path)
+    }
+
+    // Returns the outcome in case the directory no longer has the expected status.
+    fn check_dir_status(&self, descr: &DirDescriptor, expected: DirStatus) -> Option<Outcome> {
+        match self.create_crawler().get_current_state(descr) {
+            Ok((has_marker, has_children)) => {
+                if DirStatus::new(has_marker, has_children) == expected {
+                    None
+                } else {
+                    Some(self.skip_changed("check_dir_status", &descr.dir))
+                }
+            }
+            Err(error) => {
+                let outcome = Outcome::Failed(error.to_string());
+                self.notifier.error(
+                    "check_dir_status",
+                    "Directory status check failed",
+                    &fs::to_native(&descr.dir),
+                    Some(Error::Io(error)),
+                );
+                Some(outcome)
+            }
+        }
+    }
+
+    fn skip_changed(&self, target: &str, path: &PathBuf) -> Outcome {
+        self.notifier.warn(
+            target,
+            "Skipped, since changed after crawling",
+            &fs::to_native(path),
+            None,
+        );
+        Outcome::state_changed()
+    }
+
+    fn preserving_dir_times<F>(&self, dir: &Path, operation: F) -> std::io::Result<()>
+    where
+        F: FnOnce() -> std::io::Result<()>,
+    {
+        if !self.config.preserve_dir_times {
+            return operation();
+        }
+        let metadata = dir.metadata()?;
+        let (atime, mtime) = (metadata.accessed()?, metadata.modified()?);
+        operation()?;
+        self.fsaccess.set_times(dir, atime, mtime)
+    }
+
+    fn get_marker_file(&self, dir: &PathBuf) -> std::io::Result<PathBuf> {
         let mut dir = fs::get_absolute_dir(dir)?;
         dir.push(&self.config.marker_name);
         Ok(dir)
     }
+
+    // Returns the file to be staged in the Git index for the marker of the directory.
+    fn get_marker_file_path(&self, dir: &PathBuf) -> std::io::Result<PathBuf> {
+        if self.config.marker_strategy == MarkerStrategy::Manifest {
+            let dir = dir.canonicalize()?;
+            let manifests = self.manifests.lock().unwrap();
+            return fs::find_manifest(&manifests, &dir)
+                .map(|index| manifests[index].path.clone())
+                .ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::NotFound, "no manifest for directory")
+                });
+        }
+
+        self.get_marker_file(dir)
+    }
+
+    // Returns the canonical path of the file tracking the marker, as Git work trees are
+    // canonical, and whether it is to be staged. Decided by the action rather than by the
+    // file system, which is left unchanged in dry runs.
+    fn get_git_marker_file(&self, dir: &Path, marked: bool) -> std::io::Result<(PathBuf, bool)> {
+        let marker_file = self.get_marker_file_path(&dir.canonicalize()?)?;
+        let marker_file = match (marker_file.parent(), marker_file.file_name()) {
+            (Some(parent), Some(name)) => parent.canonicalize()?.join(name),
+            _ => marker_file,
+        };
+        if self.config.marker_strategy != MarkerStrategy::Manifest {
+            return Ok((marker_file, marked));
+        }
+
+        // Manifests are shared, so they are staged as long as they record any directory.
+        let manifests = self.manifests.lock().unwrap();
+        let dir = dir.canonicalize()?;
+        let staged =
+            fs::find_manifest(&manifests, &dir).is_some_and(|index| !manifests[index].is_empty());
+        Ok((marker_file, staged))
+    }
 }
 
 impl Context for DefaultContext {
@@ -153,74 +714,281 @@ impl Context for DefaultContext {
         &self.config
     }
 
+    fn get_file_system_access(&self) -> &dyn FileSystemAccess {
+        &*self.fsaccess
+    }
+
     fn get_notifier(&self) -> &Notifier {
         &*self.notifier
     }
 
+    fn get_prompter(&self) -> &dyn Prompter {
+        &*self.prompter
+    }
+
     fn crawl_dirs(&self, root_dirs: &PathList) -> DirDescriptorList {
-        let crawler = FileSystemCrawler {
-            exclude_dirs: self.config.exclude_dirs.clone(),
-            dereference_symlinks: self.config.dereference_symlinks,
-            marker_name: self.config.marker_name.clone(),
-        };
+        self.add_root_dirs(root_dirs);
+        self.read_manifests(root_dirs, true);
 
-        crawler
+        if let Some(ref paths) = self.config.selected_paths {
+            return self.crawl_dirs_shallow(&self.select_dirs(root_dirs, paths));
+        }
+
+        let mut descr_list: DirDescriptorList = self
+            .create_crawler()
             .crawl_dirs(root_dirs.clone())
             .into_iter()
             .map(|(_, descr)| descr)
-            .collect()
+            .collect();
+        descr_list.sort_by(|a, b| a.dir.cmp(&b.dir));
+        descr_list
     }
 
-    fn create_marker(&self, dir: &PathBuf, text: &String) {
-        if let Err(error) = self.create_marker_impl(dir, text) {
-            self.notifier.error(
-                "create_marker",
-                "Marker creation failed",
-                &fs::to_native(dir),
-                Some(Error::Io(error)),
-            )
-        }
+    fn crawl_dirs_shallow(&self, dirs: &PathList) -> DirDescriptorList {
+        self.add_root_dirs(dirs);
+        self.read_manifests(dirs, false);
+
+        let mut descr_list: DirDescriptorList = self
+            .create_crawler()
+            .crawl_dirs_shallow(dirs.clone())
+            .into_values()
+            .collect();
+        descr_list.sort_by(|a, b| a.dir.cmp(&b.dir));
+        descr_list
     }
 
-    fn delete_child_file(&self, file: &PathBuf) {
-        if let Err(error) = self.delete_child_file_impl(file) {
-            self.notifier.error(
-                "delete_child_file",
-                "Child file deletion failed",
-                &fs::to_native(file),
-                Some(Error::Io(error)),
-            );
+    fn create_marker(&self, descr: &DirDescriptor, text: &String) -> Outcome {
+        let dir = &descr.dir;
+        if let Some(outcome) = self.check_dir_status(descr, DirStatus::Missing) {
+            return outcome;
+        }
+
+        let result = self.create_marker_impl(dir, text);
+        let outcome = Outcome::from_result(&result);
+        match result {
+            Ok(()) => outcome,
+            // Someone else created the marker in the meantime.
+            Err(ref error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+                self.notifier.warn(
+                    "create_marker",
+                    "Marker already exists",
+                    &fs::to_native(dir),
+                    None,
+                );
+                Outcome::Skipped("marker already exists".to_owned())
+            }
+            Err(error) => {
+                self.notifier.error(
+                    "create_marker",
+                    "Marker creation failed",
+                    &fs::to_native(dir),
+                    Some(Error::Io(error)),
+                );
+                outcome
+            }
         }
     }
 
-    fn delete_child_dir(&self, dir: &PathBuf) {
-        if let Err(error) = self.delete_child_dir_impl(dir) {
+    fn convert_marker(&self, dir: &PathBuf, to: MarkerStrategy) -> Outcome {
+        let result = self.convert_marker_impl(dir, to);
+        let outcome = Outcome::from_result(&result);
+        if let Err(error) = result {
             self.notifier.error(
-                "delete_child_dir",
-                "Child deletion failed",
+                "convert_marker",
+                "Marker conversion failed",
                 &fs::to_native(dir),
                 Some(Error::Io(error)),
             );
         }
+        outcome
     }
 
-    fn delete_marker(&self, dir: &PathBuf) {
-        if let Err(error) = self.delete_marker_impl(dir) {
-            self.notifier.error(
-                "delete_marker",
-                "Marker deletion failed",
-                &fs::to_native(dir),
-                Some(Error::Io(error)),
-            );
+    fn delete_child_file(&self, file: &PathBuf) -> Outcome {
+        let result = self.delete_child_file_impl(file);
+        let outcome = Outcome::from_result(&result);
+        match result {
+            Ok(()) => outcome,
+            Err(ref error) if error.kind() == std::io::ErrorKind::NotFound => {
+                self.skip_changed("delete_child_file", file)
+            }
+            Err(error) => {
+                self.notifier.error(
+                    "delete_child_file",
+                    "Child file deletion failed",
+                    &fs::to_native(file),
+                    Some(Error::Io(error)),
+                );
+                outcome
+            }
+        }
+    }
+
+    fn delete_child_dir(&self, dir: &PathBuf) -> Outcome {
+        let result = self.delete_child_dir_impl(dir);
+        let outcome = Outcome::from_result(&result);
+        match result {
+            Ok(()) => outcome,
+            Err(ref error) if error.kind() == std::io::ErrorKind::NotFound => {
+                self.skip_changed("delete_child_dir", dir)
+            }
+            Err(error) => {
+                self.notifier.error(
+                    "delete_child_dir",
+                    "Child deletion failed",
+                    &fs::to_native(dir),
+                    Some(Error::Io(error)),
+                );
+                outcome
+            }
         }
     }
 
+    fn delete_empty_dir(&self, dir: &PathBuf) -> Outcome {
+        let result = self.delete_empty_dir_impl(dir);
+        let outcome = Outcome::from_result(&result);
+        match result {
+            Ok(()) => outcome,
+            Err(ref error) if error.kind() == std::io::ErrorKind::DirectoryNotEmpty => {
+                self.skip_changed("delete_empty_dir", dir)
+            }
+            Err(error) => {
+                self.notifier.error(
+                    "delete_empty_dir",
+                    "Empty dir deletion failed",
+                    &fs::to_native(dir),
+                    Some(Error::Io(error)),
+                );
+                outcome
+            }
+        }
+    }
+
+    fn delete_marker(&self, descr: &DirDescriptor, expected: Option<DirStatus>) -> Outcome {
+        let dir = &descr.dir;
+        if let Some(expected) = expected {
+            if let Some(outcome) = self.check_dir_status(descr, expected) {
+                return outcome;
+            }
+        }
+
+        let result = self.delete_marker_impl(dir);
+        let outcome = Outcome::from_result(&result);
+        match result {
+            Ok(()) => outcome,
+            Err(ref error) if error.kind() == std::io::ErrorKind::NotFound => {
+                self.skip_changed("delete_marker", dir)
+            }
+            Err(error) => {
+                self.notifier.error(
+                    "delete_marker",
+                    "Marker deletion failed",
+                    &fs::to_native(dir),
+                    Some(Error::Io(error)),
+                );
+                outcome
+            }
+        }
+    }
+
+    fn write_manifests(&self) -> Vec<(PathBuf, Outcome)> {
+        let mut outcomes = vec![];
+        for manifest in self.manifests.lock().unwrap().iter_mut() {
+            if !manifest.is_changed() {
+                continue;
+            }
+            let result = manifest.write(&*self.fsaccess);
+            outcomes.push((manifest.path.clone(), Outcome::from_result(&result)));
+            match result {
+                Ok(()) => self.notifier.info(
+                    "write_manifest",
+                    "Manifest written",
+                    &fs::to_native(&manifest.path),
+                    None,
+                ),
+                Err(error) => self.notifier.error(
+                    "write_manifest",
+                    "Manifest writing failed",
+                    &fs::to_native(&manifest.path),
+                    Some(Error::Io(error)),
+                ),
+            }
+        }
+        outcomes
+    }
+
     fn get_root_dir<'a>(
         &self,
         dir: &PathBuf,
         root_dirs: &'a PathList,
     ) -> std::io::Result<Option<&'a PathBuf>> {
         let dir = fs::get_absolute_dir(dir)?;
-        Ok(root_dirs.iter().find(|root_dir| dir.starts_with(root_dir)))
+        Ok(root_dirs
+            .iter()
+            .find(|root_dir| match root_dir.canonicalize() {
+                Ok(root_dir) => dir.starts_with(root_dir),
+                Err(_) => dir.starts_with(root_dir),
+            }))
+    }
+
+    fn update_git_index(
+        &self,
+        root_dirs: &PathList,
+        marked_dirs: &PathList,
+        unmarked_dirs: &PathList,
+    ) {
+        let repos = self.discover_git_repositories(root_dirs);
+
+        let mut marker_files_by_repo: Vec<(PathList, PathList)> =
+            vec![(vec![], vec![]); repos.len()];
+        let dirs = marked_dirs
+            .iter()
+            .map(|dir| (dir, true))
+            .chain(unmarked_dirs.iter().map(|dir| (dir, false)));
+        for (dir, marked) in dirs {
+            let (marker_file, staged) = match self.get_git_marker_file(dir, marked) {
+                Ok(marker_file) => marker_file,
+                Err(error) => {
+                    self.notifier.error(
+                        "update_git_index",
+                        "Marker path resolution failed",
+                        &fs::to_native(dir),
+                        Some(Error::Io(error)),
+                    );
+                    continue;
+                }
+            };
+            match repos.iter().position(|repo| repo.contains(&marker_file)) {
+                Some(index) => {
+                    let (ref mut staged_files, ref mut unstaged_files) =
+                        marker_files_by_repo[index];
+                    let marker_files = if staged { staged_files } else { unstaged_files };
+                    // Directories recorded in the same manifest share their marker file.
+                    if !marker_files.contains(&marker_file) {
+                        marker_files.push(marker_file);
+                    }
+                }
+                None => self.notifier.error(
+                    "update_git_index",
+                    "Marker outside of Git work tree",
+                    &fs::to_native(&marker_file),
+                    None,
+                ),
+            }
+        }
+
+        for (repo, (staged, unstaged)) in repos.iter().zip(marker_files_by_repo.iter()) {
+            if let Err(error) = self.update_git_index_impl(repo, staged, unstaged) {
+                self.notifier.error(
+                    "update_git_index",
+                    "Git index update failed",
+                    &fs::to_native(&repo.work_tree),
+                    Some(Error::Io(error)),
+                );
+            }
+        }
+    }
+
+    fn take_retry_records(&self) -> Vec<RetryRecord> {
+        self.retry_log.take()
     }
 }
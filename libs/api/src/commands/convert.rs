@@ -0,0 +1,73 @@
+use super::*;
+
+use fs::MarkerStrategy;
+use rayon::prelude::*;
+use std::sync::Mutex;
+
+#[derive(PartialEq, Debug)]
+pub struct Convert {
+    pub dry_run: bool,
+    pub root_dirs: PathList,
+    pub to: MarkerStrategy,
+}
+
+impl Default for Convert {
+    fn default() -> Convert {
+        Convert::new()
+    }
+}
+
+impl Convert {
+    pub fn new() -> Convert {
+        Convert {
+            dry_run: false,
+            root_dirs: Config::default_root_dirs(),
+            to: MarkerStrategy::Xattr,
+        }
+    }
+}
+
+impl Command for Convert {
+    fn execute(&self, ctx: &dyn Context) -> Result<Report> {
+        if self.to == MarkerStrategy::Manifest
+            || ctx.get_config().marker_strategy == MarkerStrategy::Manifest
+        {
+            return Err(Error::Message(
+                "Markers can only be converted between files and extended attributes",
+            ));
+        }
+
+        check_root_dirs(ctx, &self.root_dirs)?;
+
+        let mut report = Report::new();
+        let descr_list = crawl_dirs(ctx, &self.root_dirs, &mut report);
+        let report = Mutex::new(report);
+
+        // Convert markers of the other kind.
+        descr_list.par_iter().for_each(|descr| {
+            let convertible = match self.to {
+                MarkerStrategy::File => descr.has_marker_xattr(),
+                _ => descr.has_marker_file(),
+            };
+            if convertible {
+                let outcome = ctx.convert_marker(&descr.dir, self.to);
+                report
+                    .lock()
+                    .unwrap()
+                    .record(Action::ConvertMarker, &descr.dir, outcome);
+            }
+        });
+
+        let mut report = report.into_inner().unwrap();
+        report.sort();
+        Ok(report)
+    }
+
+    fn get_root_dirs(&self) -> Option<&PathList> {
+        Some(&self.root_dirs)
+    }
+
+    fn reads_all_marker_backends(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,182 @@
+use super::*;
+
+use fs;
+use fs::{DeletePolicy, FileOptions};
+use git::GitRepository;
+use std::collections::{BTreeSet, HashSet};
+
+const HOOK_SIGNATURE: &str = "Installed by markemptydirs";
+
+#[derive(PartialEq, Debug)]
+pub struct HookInstall {
+    pub force: bool,
+    pub repo_dir: PathBuf,
+}
+
+impl Default for HookInstall {
+    fn default() -> HookInstall {
+        HookInstall::new()
+    }
+}
+
+impl HookInstall {
+    pub fn new() -> HookInstall {
+        HookInstall {
+            force: false,
+            repo_dir: PathBuf::from("."),
+        }
+    }
+
+    fn create_script(&self, cfg: &Config) -> String {
+        let exclude_dirs: Vec<_> = cfg.exclude_dirs.iter().map(fs::to_native).collect();
+        format!(
+            "#!/bin/sh\n\
+             # {}: checks markers of directories touched by the commit.\n\
+             exec {} --exclude {} --marker-name {} hook run\n",
+            HOOK_SIGNATURE,
+            quote_for_shell(&fs::to_native(&cfg.executable_file)),
+            quote_for_shell(&exclude_dirs.join(":")),
+            quote_for_shell(&cfg.marker_name),
+        )
+    }
+}
+
+impl Command for HookInstall {
+    fn execute(&self, ctx: &dyn Context) -> Result<Report> {
+        let repo = GitRepository::discover(&self.repo_dir).map_err(Error::Io)?;
+        let hooks_dir = repo.hooks_dir().map_err(Error::Io)?;
+        let hook_file = hooks_dir.join("pre-commit");
+
+        if hook_file.exists() && !self.force {
+            let installed_by_us = std::fs::read_to_string(&hook_file)
+                .map(|script| script.contains(HOOK_SIGNATURE))
+                .unwrap_or(false);
+            if !installed_by_us {
+                ctx.get_notifier().error(
+                    "hook_install",
+                    "Foreign hook found",
+                    &fs::to_native(&hook_file),
+                    None,
+                );
+                return Err(Error::Message(
+                    "Pre-commit hook already exists; use --force to overwrite it",
+                ));
+            }
+        }
+
+        let fsaccess = ctx.get_file_system_access();
+        let options = FileOptions {
+            mode: Some(0o755),
+            ..FileOptions::default()
+        };
+        fsaccess.create_dir_all(&hooks_dir).map_err(Error::Io)?;
+        fsaccess
+            .replace_file(&hook_file, &self.create_script(ctx.get_config()), &options)
+            .map_err(Error::Io)?;
+
+        ctx.get_notifier().info(
+            "hook_install",
+            "Hook installed",
+            &fs::to_native(&hook_file),
+            None,
+        );
+
+        let mut report = Report::new();
+        report.record(Action::InstallHook, &hook_file, Outcome::Done);
+        Ok(report)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct HookRun {
+    pub repo_dir: PathBuf,
+}
+
+impl Default for HookRun {
+    fn default() -> HookRun {
+        HookRun::new()
+    }
+}
+
+impl HookRun {
+    pub fn new() -> HookRun {
+        HookRun {
+            repo_dir: PathBuf::from("."),
+        }
+    }
+}
+
+impl Command for HookRun {
+    fn execute(&self, ctx: &dyn Context) -> Result<Report> {
+        let repo = GitRepository::discover(&self.repo_dir).map_err(Error::Io)?;
+        let dirs = get_touched_dirs(&repo, &repo.staged_paths().map_err(Error::Io)?);
+        let index_files: HashSet<_> = repo.index_files().map_err(Error::Io)?.into_iter().collect();
+
+        let mut report = Report::new();
+        let descr_list = ctx.crawl_dirs_shallow(&dirs);
+        report.classify(&descr_list);
+
+        // Judge what is being committed, that is emptiness and marker files as found in the
+        // index, whatever the working tree contains.
+        let marker_name = &ctx.get_config().marker_name;
+        let mut outdated_dirs: Vec<_> = descr_list
+            .into_iter()
+            .filter_map(|descr| {
+                let marker_file = descr.dir.join(marker_name);
+                // Git knows nothing about markers kept in attributes or manifests.
+                let has_marker = index_files.contains(&marker_file)
+                    || (descr.has_marker() && !descr.has_marker_file());
+                let has_children = index_files
+                    .iter()
+                    .any(|file| file.starts_with(&descr.dir) && *file != marker_file);
+                if has_marker != has_children {
+                    return None;
+                }
+                // Directives may keep markers in non-empty directories.
+                if has_marker && descr.get_directives().delete == DeletePolicy::Never {
+                    return None;
+                }
+                Some((descr.dir, has_marker))
+            })
+            .collect();
+        outdated_dirs.sort();
+
+        for (dir, has_marker) in &outdated_dirs {
+            let info = if *has_marker {
+                "Marker clashing with non-empty directory"
+            } else {
+                "Marker missing in empty directory"
+            };
+            ctx.get_notifier()
+                .error("hook_run", info, &fs::to_native(dir), None);
+            report.add_error(dir, info.to_owned());
+        }
+
+        if !outdated_dirs.is_empty() {
+            return Err(Error::Message(
+                "Markers are out of date; run 'markemptydirs update --git' and commit again",
+            ));
+        }
+        report.sort();
+        Ok(report)
+    }
+}
+
+fn get_touched_dirs(repo: &GitRepository, paths: &PathList) -> PathList {
+    // Adding or removing a path may change the emptiness of all its ancestors.
+    let dirs: BTreeSet<_> = paths
+        .iter()
+        .flat_map(|path| {
+            path.ancestors()
+                .skip(1)
+                .take_while(|dir| repo.contains(dir))
+        })
+        .filter(|dir| dir.is_dir())
+        .map(|dir| dir.to_owned())
+        .collect();
+    dirs.into_iter().collect()
+}
+
+fn quote_for_shell(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
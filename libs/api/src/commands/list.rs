@@ -1,6 +1,7 @@
 use super::*;
 
 use fs;
+use fs::MarkerStrategy;
 use rayon::prelude::*;
 
 #[derive(PartialEq, Debug)]
@@ -20,6 +21,7 @@ pub struct List {
 struct ListStatistics {
     pub dir: PathBuf,
     pub marker_found: bool,
+    pub marker_backends: Vec<MarkerStrategy>,
     pub marker_required: bool,
     pub child_count: usize,
     pub dir_count: usize,
@@ -35,12 +37,13 @@ impl List {
 }
 
 impl Command for List {
-    fn execute(&self, ctx: &Context) -> Result<()> {
-        let mut statistics_list: Vec<_> = ctx
-            .crawl_dirs(&self.root_dirs)
+    fn execute(&self, ctx: &dyn Context) -> Result<Report> {
+        let mut report = Report::new();
+        let mut statistics_list: Vec<_> = crawl_dirs(ctx, &self.root_dirs, &mut report)
             .into_par_iter()
             .map(|descr| ListStatistics {
                 marker_found: descr.has_marker(),
+                marker_backends: descr.get_marker_backends(),
                 marker_required: !descr.has_children(),
                 child_count: descr.get_child_count(),
                 dir_count: descr.get_sub_directory_count(),
@@ -55,10 +58,27 @@ impl Command for List {
             .as_mut_slice()
             .par_sort_unstable_by_key(|stat| stat.dir.clone());
 
-        for stat in statistics_list {
-            println!("{:?}", stat);
+        // The JSON report contains the classification instead.
+        if !ctx.get_config().json_report {
+            for stat in statistics_list {
+                // Interrupted conversions may leave markers of several backends.
+                if stat.marker_backends.len() > 1 {
+                    ctx.get_notifier().warn(
+                        "list",
+                        "Markers of several backends found",
+                        &fs::to_native(&stat.dir),
+                        None,
+                    );
+                }
+                println!("{:?}", stat);
+            }
         }
 
-        Ok(())
+        report.sort();
+        Ok(report)
+    }
+
+    fn reads_all_marker_backends(&self) -> bool {
+        true
     }
 }
@@ -0,0 +1,84 @@
+use super::*;
+
+use fs;
+use fs::Manifest;
+
+#[derive(PartialEq, Debug)]
+pub struct Materialize {
+    pub dry_run: bool,
+    pub root_dirs: PathList,
+}
+
+impl Default for Materialize {
+    fn default() -> Materialize {
+        Materialize::new()
+    }
+}
+
+impl Materialize {
+    pub fn new() -> Materialize {
+        Materialize {
+            dry_run: false,
+            root_dirs: Config::default_root_dirs(),
+        }
+    }
+}
+
+impl Command for Materialize {
+    fn execute(&self, ctx: &dyn Context) -> Result<Report> {
+        check_root_dirs(ctx, &self.root_dirs)?;
+
+        let mut report = Report::new();
+        let fsaccess = ctx.get_file_system_access();
+        let notifier = ctx.get_notifier();
+        let manifest_name = &ctx.get_config().manifest_name;
+
+        for root_dir in &self.root_dirs {
+            let root_dir = root_dir.canonicalize().map_err(Error::Io)?;
+            let manifest = match Manifest::read(&root_dir, manifest_name) {
+                Ok(manifest) => manifest,
+                Err(error) => {
+                    let manifest_file = &root_dir.join(manifest_name);
+                    report.add_error(manifest_file, error.to_string());
+                    notifier.error(
+                        "materialize",
+                        "Manifest reading failed",
+                        &fs::to_native(manifest_file),
+                        Some(Error::Io(error)),
+                    );
+                    continue;
+                }
+            };
+
+            for dir in manifest.get_dirs() {
+                if dir.is_dir() {
+                    continue;
+                }
+                let result = fs::check_inside_root_dirs(std::slice::from_ref(&root_dir), &dir)
+                    .and_then(|_| fsaccess.create_dir_all(&dir));
+                report.record(Action::CreateDir, &dir, Outcome::from_result(&result));
+                match result {
+                    Ok(()) => notifier.info(
+                        "materialize",
+                        "Directory created",
+                        &fs::to_native(&dir),
+                        None,
+                    ),
+                    Err(error) => notifier.error(
+                        "materialize",
+                        "Directory creation failed",
+                        &fs::to_native(&dir),
+                        Some(Error::Io(error)),
+                    ),
+                }
+            }
+        }
+
+        report.sort();
+        Ok(report)
+    }
+
+    fn get_root_dirs(&self) -> Option<&PathList> {
+        Some(&self.root_dirs)
+    }
+}
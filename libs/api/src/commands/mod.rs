@@ -1,30 +1,236 @@
-pub use fs::{Path, PathBuf, PathList};
+pub use fs::{DirDescriptor, DirDescriptorList, Path, PathBuf, PathList};
 
+use fs;
 use Error;
 
+mod apply;
+pub use self::apply::*;
+
 mod clean;
 pub use self::clean::*;
 
+mod confirmation;
+pub use self::confirmation::*;
+
 mod context;
 pub use self::context::*;
 
+mod convert;
+pub use self::convert::*;
+
+mod hook;
+pub use self::hook::*;
+
 mod list;
 pub use self::list::*;
 
+mod materialize;
+pub use self::materialize::*;
+
+mod prune;
+pub use self::prune::*;
+
 mod purge;
 pub use self::purge::*;
 
+mod report;
+pub use self::report::*;
+
+mod trash;
+pub use self::trash::*;
+
 mod update;
 pub use self::update::*;
 
+mod variables;
+pub use self::variables::*;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
-pub trait Command: std::fmt::Debug {
-    fn execute(&self, ctx: &context::Context) -> Result<()>;
+pub trait Command: std::fmt::Debug + Sync {
+    fn execute(&self, ctx: &dyn context::Context) -> Result<Report>;
+
+    // Root directories modified by the command, which are locked while it runs.
+    fn get_root_dirs(&self) -> Option<&PathList> {
+        None
+    }
+
+    // Whether the command looks for markers of all backends rather than the configured one.
+    fn reads_all_marker_backends(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug)]
 pub enum Execution {
-    DryRun(Box<Command>),
-    Run(Box<Command>),
+    DryRun(Box<dyn Command>),
+    Plan(Box<dyn Command>, PathBuf),
+    Run(Box<dyn Command>),
+}
+
+// Keeps a marker its directives protect from deletion.
+fn keep_marker(ctx: &dyn context::Context, descr: &DirDescriptor) -> Outcome {
+    ctx.get_notifier().info(
+        "delete_marker",
+        "Marker kept by directive",
+        &fs::to_native(&descr.dir),
+        None,
+    );
+    Outcome::Skipped("kept by directive".to_owned())
+}
+
+// Directories recorded in a manifest are trusted, since there is no marker to mistake.
+fn is_signed_marker(ctx: &dyn context::Context, descr: &DirDescriptor) -> bool {
+    !(descr.has_marker_file() || descr.has_marker_xattr())
+        || descr.get_directives().is_signed_by(ctx.get_appinfo().name)
+}
+
+fn ignore_unsigned_marker(ctx: &dyn context::Context, descr: &DirDescriptor) -> Outcome {
+    ctx.get_notifier().warn(
+        "strict",
+        "Unsigned marker ignored",
+        &fs::to_native(&descr.dir),
+        None,
+    );
+    Outcome::Skipped("unsigned marker".to_owned())
+}
+
+// Returns the content to be purged from a marked directory, flagging directories.
+// Directories containing protected directories are kept.
+fn get_purgeable_children(
+    descr: &DirDescriptor,
+    protected_dirs: &PathList,
+) -> Vec<(PathBuf, bool)> {
+    let mut children = vec![];
+    let directives = descr.get_directives();
+    // Directories that are not crawled, like excluded ones, are kept.
+    descr.for_each_file(|entry| {
+        if !entry.file_type().map(|ft| ft.is_dir()).unwrap_or(true)
+            && !directives.keeps(&entry.file_name())
+        {
+            children.push((entry.path(), false));
+        }
+    });
+    descr.for_each_sub_direntry(|entry| {
+        let path = &entry.path();
+        if !directives.keeps(&entry.file_name())
+            && !protected_dirs.iter().any(|dir| dir.starts_with(path))
+        {
+            children.push((entry.path(), true));
+        }
+    });
+    children
+}
+
+// Refuses to modify file system roots, the home directory or system directories unless
+// forced to.
+fn check_root_dirs(ctx: &dyn context::Context, root_dirs: &PathList) -> Result<()> {
+    if ctx.get_config().force {
+        return Ok(());
+    }
+    let mut dangerous = false;
+    for root_dir in root_dirs {
+        if fs::is_dangerous_root_dir(root_dir) {
+            ctx.get_notifier().error(
+                "check_root_dirs",
+                "Dangerous root directory",
+                &fs::to_native(root_dir),
+                None,
+            );
+            dangerous = true;
+        }
+    }
+    if dangerous {
+        return Err(Error::Message(
+            "Refusing to operate on dangerous root directories; use --force to do so anyway",
+        ));
+    }
+    Ok(())
+}
+
+// Prevents concurrent runs from racing on the same root directories.
+pub fn lock_root_dirs(
+    ctx: &dyn context::Context,
+    root_dirs: &PathList,
+) -> Result<Vec<fs::RootLock>> {
+    let config = ctx.get_config();
+    let notifier = ctx.get_notifier();
+
+    // Lock in a fixed order, so that runs on overlapping root directories cannot deadlock.
+    let mut root_dirs: Vec<_> = root_dirs
+        .iter()
+        .filter_map(|root_dir| root_dir.canonicalize().ok())
+        .collect();
+    root_dirs.sort();
+    root_dirs.dedup();
+    // The lock of a root directory covers the ones nested in it, which would conflict.
+    let mut outer_root_dirs: PathList = vec![];
+    for root_dir in root_dirs {
+        if !outer_root_dirs
+            .iter()
+            .any(|outer| root_dir.starts_with(outer))
+        {
+            outer_root_dirs.push(root_dir);
+        }
+    }
+    let root_dirs = outer_root_dirs;
+
+    let mut locks = vec![];
+    for root_dir in &root_dirs {
+        let lock = fs::RootLock::acquire(root_dir, config.lock_timeout, |holder| {
+            notifier.warn(
+                "lock",
+                "Waiting for lock",
+                &format!("{}, held by {}", fs::to_native(root_dir), holder),
+                None,
+            );
+            notifier.flush();
+        });
+        match lock {
+            Ok(lock) => locks.push(lock),
+            Err(ref error) if error.kind() == std::io::ErrorKind::WouldBlock => {
+                notifier.error(
+                    "lock",
+                    "Root directory locked",
+                    &format!("{}, {}", fs::to_native(root_dir), error),
+                    None,
+                );
+                notifier.flush();
+                return Err(Error::Message(
+                    "Another run is in progress; use --lock-timeout to wait for it",
+                ));
+            }
+            Err(error) => {
+                notifier.error(
+                    "lock",
+                    "Root directory locking failed",
+                    &fs::to_native(root_dir),
+                    Some(Error::Io(error)),
+                );
+                notifier.flush();
+                return Err(Error::Message("Root directory locking failed"));
+            }
+        }
+    }
+    Ok(locks)
+}
+
+// Crawls directories while recording their classification and the time taken.
+fn crawl_dirs(
+    ctx: &dyn context::Context,
+    root_dirs: &PathList,
+    report: &mut Report,
+) -> DirDescriptorList {
+    let start = std::time::Instant::now();
+    let descr_list = ctx.crawl_dirs(root_dirs);
+    report.timings.crawl += start.elapsed();
+    report.classify(&descr_list);
+    descr_list
+}
+
+// Writes manifests changed in manifest mode while recording the outcomes.
+fn write_manifests(ctx: &dyn context::Context, report: &mut Report) {
+    for (manifest_file, outcome) in ctx.write_manifests() {
+        report.record(Action::WriteManifest, &manifest_file, outcome);
+    }
 }
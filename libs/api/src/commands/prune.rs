@@ -0,0 +1,67 @@
+use super::*;
+
+use std::collections::{HashMap, HashSet};
+
+#[derive(PartialEq, Debug)]
+pub struct Prune {
+    pub dry_run: bool,
+    pub root_dirs: PathList,
+}
+
+impl Default for Prune {
+    fn default() -> Prune {
+        Prune::new()
+    }
+}
+
+impl Prune {
+    pub fn new() -> Prune {
+        Prune {
+            dry_run: false,
+            root_dirs: Config::default_root_dirs(),
+        }
+    }
+}
+
+impl Command for Prune {
+    fn execute(&self, ctx: &dyn Context) -> Result<Report> {
+        check_root_dirs(ctx, &self.root_dirs)?;
+
+        let mut report = Report::new();
+        let descr_list = crawl_dirs(ctx, &self.root_dirs, &mut report);
+
+        let root_dirs: HashSet<_> = self
+            .root_dirs
+            .iter()
+            .filter_map(|root_dir| root_dir.canonicalize().ok())
+            .collect();
+
+        // Visit children before their parents, so that deleting the last child of a
+        // directory makes the directory itself a candidate for deletion.
+        let mut deleted_child_counts: HashMap<PathBuf, usize> = HashMap::new();
+        for descr in descr_list.iter().rev() {
+            if descr.has_marker() || root_dirs.contains(&descr.dir) {
+                continue;
+            }
+            let deleted_child_count = deleted_child_counts.get(&descr.dir).cloned().unwrap_or(0);
+            if descr.get_child_count() > deleted_child_count {
+                continue;
+            }
+
+            let outcome = ctx.delete_empty_dir(&descr.dir);
+            if outcome == Outcome::Done {
+                if let Some(parent) = descr.dir.parent() {
+                    *deleted_child_counts.entry(parent.to_owned()).or_insert(0) += 1;
+                }
+            }
+            report.record(Action::DeleteEmptyDir, &descr.dir, outcome);
+        }
+
+        report.sort();
+        Ok(report)
+    }
+
+    fn get_root_dirs(&self) -> Option<&PathList> {
+        Some(&self.root_dirs)
+    }
+}
@@ -1,23 +1,146 @@
 use super::*;
 
+use fs;
+use fs::PurgePolicy;
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
 #[derive(PartialEq, Debug)]
 pub struct Purge {
+    pub confirmation: Confirmation,
     pub dry_run: bool,
+    pub max_file_count: Option<usize>,
     pub root_dirs: PathList,
+    pub strict: bool,
+}
+
+impl Default for Purge {
+    fn default() -> Purge {
+        Purge::new()
+    }
 }
 
 impl Purge {
+    pub fn default_max_file_count() -> Option<usize> {
+        Some(10000)
+    }
+
     pub fn new() -> Purge {
         Purge {
+            confirmation: Confirmation::Never,
             dry_run: false,
+            max_file_count: Purge::default_max_file_count(),
             root_dirs: Config::default_root_dirs(),
+            strict: false,
         }
     }
 }
 
 impl Command for Purge {
-    fn execute(&self, _ctx: &Context) -> Result<()> {
-        // TODO Implement Purge command.
-        Err(Error::Message("To be implementd"))
+    fn execute(&self, ctx: &dyn Context) -> Result<Report> {
+        check_root_dirs(ctx, &self.root_dirs)?;
+
+        let mut report = Report::new();
+        let (protected_descrs, mut descr_list): (Vec<_>, Vec<_>) =
+            crawl_dirs(ctx, &self.root_dirs, &mut report)
+                .into_iter()
+                .filter(|descr| descr.has_marker())
+                .partition(|descr| descr.get_directives().purge == PurgePolicy::Never);
+        let protected_dirs = protected_descrs
+            .into_iter()
+            .map(|descr| descr.dir)
+            .collect();
+
+        // In strict mode, files merely named like markers are left alone.
+        if self.strict {
+            let (signed_descrs, unsigned_descrs): (Vec<_>, Vec<_>) = descr_list
+                .into_iter()
+                .partition(|descr| is_signed_marker(ctx, descr));
+            for descr in &unsigned_descrs {
+                let outcome = ignore_unsigned_marker(ctx, descr);
+                report.record(Action::PurgeDir, &descr.dir, outcome);
+            }
+            descr_list = signed_descrs;
+        }
+        descr_list.sort_by(|a, b| a.dir.cmp(&b.dir));
+
+        // Nested marked directories vanish together with their marked ancestor.
+        let mut last_purged_dir: Option<PathBuf> = None;
+        descr_list.retain(|descr| {
+            if let Some(ref dir) = last_purged_dir {
+                if descr.dir.starts_with(dir) {
+                    return false;
+                }
+            }
+            last_purged_dir = Some(descr.dir.clone());
+            true
+        });
+
+        let planned_dirs: Vec<_> = descr_list
+            .par_iter()
+            .map(|descr| {
+                let mut planned = PlannedDir {
+                    dir: descr.dir.clone(),
+                    file_count: 0,
+                    size: 0,
+                };
+                for child in get_purgeable_children(descr, &protected_dirs) {
+                    if let Ok((file_count, size)) = fs::get_disk_usage(&child.0) {
+                        planned.file_count += file_count;
+                        planned.size += size;
+                    }
+                }
+                planned
+            })
+            .collect();
+
+        let file_count: usize = planned_dirs.iter().map(|planned| planned.file_count).sum();
+        if let Some(max_file_count) = self.max_file_count {
+            if file_count > max_file_count {
+                ctx.get_notifier().error(
+                    "purge",
+                    "Number of files to purge exceeds limit",
+                    &format!("{} > {}", file_count, max_file_count),
+                    None,
+                );
+                return Err(Error::Message(
+                    "Refusing to purge that many files; raise --max-files or use --no-file-limit",
+                ));
+            }
+        }
+
+        let selected_dirs: HashSet<_> = confirm_planned_dirs(
+            ctx,
+            "Purge",
+            &self.root_dirs,
+            planned_dirs,
+            self.confirmation,
+        )?
+        .into_iter()
+        .collect();
+
+        let report = Mutex::new(report);
+        descr_list
+            .par_iter()
+            .filter(|descr| selected_dirs.contains(&descr.dir))
+            .for_each(|descr| {
+                for (child, is_dir) in get_purgeable_children(descr, &protected_dirs) {
+                    let (action, outcome) = if is_dir {
+                        (Action::DeleteChildDir, ctx.delete_child_dir(&child))
+                    } else {
+                        (Action::DeleteChildFile, ctx.delete_child_file(&child))
+                    };
+                    report.lock().unwrap().record(action, &child, outcome);
+                }
+            });
+
+        let mut report = report.into_inner().unwrap();
+        report.sort();
+        Ok(report)
+    }
+
+    fn get_root_dirs(&self) -> Option<&PathList> {
+        Some(&self.root_dirs)
     }
 }
@@ -0,0 +1,194 @@
+use super::*;
+
+use fs::{MarkerStrategy, RetryRecord};
+use serde::Serializer;
+use serde_json;
+use std::time::Duration;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    ApplyOperation,
+    CreateDir,
+    ConvertMarker,
+    CreateMarker,
+    DeleteChildDir,
+    DeleteChildFile,
+    DeleteEmptyDir,
+    DeleteMarker,
+    DeleteTrashedPath,
+    InstallHook,
+    PurgeDir,
+    RestorePath,
+    WriteManifest,
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+#[serde(rename_all = "snake_case", tag = "status", content = "reason")]
+pub enum Outcome {
+    Done,
+    Skipped(String),
+    Failed(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DirStatus {
+    // Marker in non-empty directory.
+    Clashing,
+    // Marker in otherwise empty directory.
+    Correct,
+    // No marker in empty directory.
+    Missing,
+    // No marker in non-empty directory.
+    Unmarked,
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct ActionRecord {
+    pub action: Action,
+    pub path: PathBuf,
+    pub outcome: Outcome,
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct Classification {
+    pub dir: PathBuf,
+    pub status: DirStatus,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub marker_backends: Vec<MarkerStrategy>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct PathError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Debug)]
+pub struct Timings {
+    #[serde(serialize_with = "serialize_seconds")]
+    pub crawl: Duration,
+    #[serde(serialize_with = "serialize_seconds")]
+    pub total: Duration,
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Debug)]
+pub struct Report {
+    // Actions were only planned, not taken.
+    pub dry_run: bool,
+    pub actions: Vec<ActionRecord>,
+    pub classifications: Vec<Classification>,
+    pub errors: Vec<PathError>,
+    // Operations retried after transient errors, whether in the end successfully or not.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub retries: Vec<RetryRecord>,
+    pub timings: Timings,
+}
+
+impl Report {
+    pub fn new() -> Report {
+        Report::default()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.errors.is_empty() && self.retries.iter().all(|record| record.succeeded)
+    }
+
+    pub fn classify(&mut self, descr_list: &DirDescriptorList) {
+        self.classifications
+            .extend(descr_list.iter().map(|descr| Classification {
+                dir: descr.dir.clone(),
+                status: DirStatus::new(descr.has_marker(), descr.has_children()),
+                marker_backends: descr.get_marker_backends(),
+            }));
+    }
+
+    pub fn record(&mut self, action: Action, path: &Path, outcome: Outcome) {
+        if let Outcome::Failed(ref message) = outcome {
+            self.add_error(path, message.clone());
+        }
+        self.actions.push(ActionRecord {
+            action,
+            path: path.to_owned(),
+            outcome,
+        });
+    }
+
+    pub fn add_error(&mut self, path: &Path, message: String) {
+        self.errors.push(PathError {
+            path: path.to_owned(),
+            message,
+        });
+    }
+
+    // Returns the paths the action was taken on successfully, or planned for in dry runs.
+    pub fn get_done_paths(&self, action: Action) -> PathList {
+        self.actions
+            .iter()
+            .filter(|record| record.action == action && record.outcome == Outcome::Done)
+            .map(|record| record.path.clone())
+            .collect()
+    }
+
+    // Removes actions skipped since their directories changed, returning the directories.
+    pub fn take_changed_dirs(&mut self) -> PathList {
+        let (changed, actions): (Vec<_>, Vec<_>) = self
+            .actions
+            .drain(..)
+            .partition(|record| record.outcome.is_state_changed());
+        self.actions = actions;
+        let mut dirs: PathList = changed.into_iter().map(|record| record.path).collect();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    // Orders entries by path, independent of parallel execution.
+    pub fn sort(&mut self) {
+        self.actions.sort_by(|a, b| a.path.cmp(&b.path));
+        self.classifications.sort_by(|a, b| a.dir.cmp(&b.dir));
+        self.errors.sort_by(|a, b| a.path.cmp(&b.path));
+        self.retries.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl DirStatus {
+    pub fn new(has_marker: bool, has_children: bool) -> DirStatus {
+        match (has_marker, has_children) {
+            (true, true) => DirStatus::Clashing,
+            (true, false) => DirStatus::Correct,
+            (false, false) => DirStatus::Missing,
+            (false, true) => DirStatus::Unmarked,
+        }
+    }
+}
+
+impl Outcome {
+    // The directory changed between crawling and acting on it.
+    pub fn state_changed() -> Outcome {
+        Outcome::Skipped("state changed".to_owned())
+    }
+
+    pub fn is_state_changed(&self) -> bool {
+        *self == Outcome::state_changed()
+    }
+
+    pub fn from_result<T, E: std::fmt::Display>(result: &std::result::Result<T, E>) -> Outcome {
+        match *result {
+            Ok(_) => Outcome::Done,
+            Err(ref error) => Outcome::Failed(error.to_string()),
+        }
+    }
+}
+
+fn serialize_seconds<S: Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
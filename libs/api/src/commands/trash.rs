@@ -0,0 +1,163 @@
+use super::*;
+
+use chrono::Local;
+use fs;
+use fs::{Trash, TrashLocation};
+use std::collections::HashSet;
+use std::time::Duration;
+
+#[derive(PartialEq, Debug)]
+pub struct Restore {
+    pub dry_run: bool,
+    pub root_dirs: PathList,
+}
+
+impl Default for Restore {
+    fn default() -> Restore {
+        Restore::new()
+    }
+}
+
+impl Restore {
+    pub fn new() -> Restore {
+        Restore {
+            dry_run: false,
+            root_dirs: Config::default_root_dirs(),
+        }
+    }
+}
+
+impl Command for Restore {
+    fn execute(&self, ctx: &dyn Context) -> Result<Report> {
+        let mut report = Report::new();
+        let trash = get_trash(ctx);
+        let fsaccess = ctx.get_file_system_access();
+        let notifier = ctx.get_notifier();
+
+        let root_dirs: PathList = self
+            .root_dirs
+            .iter()
+            .map(|root_dir| root_dir.canonicalize())
+            .collect::<std::io::Result<_>>()
+            .map_err(Error::Io)?;
+
+        let mut entries: Vec<_> = trash
+            .get_entries()
+            .map_err(Error::Io)?
+            .into_iter()
+            .filter(|entry| {
+                root_dirs
+                    .iter()
+                    .any(|root_dir| entry.original_path.starts_with(root_dir))
+            })
+            .collect();
+
+        // Restore the most recently trashed version of each path.
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.deletion_date));
+        let mut restored_paths = HashSet::new();
+        for entry in entries {
+            let original_path = &fs::to_native(&entry.original_path);
+            if restored_paths.contains(&entry.original_path) {
+                notifier.warn(
+                    "restore",
+                    "Older version kept in trash",
+                    original_path,
+                    None,
+                );
+                report.record(
+                    Action::RestorePath,
+                    &entry.original_path,
+                    Outcome::Skipped("older version kept in trash".to_owned()),
+                );
+                continue;
+            }
+            let result = trash.restore(fsaccess, &entry);
+            report.record(
+                Action::RestorePath,
+                &entry.original_path,
+                Outcome::from_result(&result),
+            );
+            match result {
+                Ok(()) => {
+                    notifier.info("restore", "Path restored", original_path, None);
+                    restored_paths.insert(entry.original_path);
+                }
+                Err(error) => notifier.error(
+                    "restore",
+                    "Path restoration failed",
+                    original_path,
+                    Some(Error::Io(error)),
+                ),
+            }
+        }
+
+        trash.remove_empty_trash_dirs(fsaccess).map_err(Error::Io)?;
+        report.sort();
+        Ok(report)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct TrashEmpty {
+    pub dry_run: bool,
+    pub older_than: Duration,
+}
+
+impl Default for TrashEmpty {
+    fn default() -> TrashEmpty {
+        TrashEmpty::new()
+    }
+}
+
+impl TrashEmpty {
+    pub fn new() -> TrashEmpty {
+        TrashEmpty {
+            dry_run: false,
+            older_than: Duration::from_secs(0),
+        }
+    }
+}
+
+impl Command for TrashEmpty {
+    fn execute(&self, ctx: &dyn Context) -> Result<Report> {
+        let mut report = Report::new();
+        let trash = get_trash(ctx);
+        let fsaccess = ctx.get_file_system_access();
+        let notifier = ctx.get_notifier();
+
+        let older_than = chrono::Duration::from_std(self.older_than)
+            .map_err(|_| Error::Message("Age out of range"))?;
+        let deadline = Local::now() - older_than;
+
+        for entry in trash.get_entries().map_err(Error::Io)? {
+            if entry.deletion_date > deadline {
+                continue;
+            }
+            let original_path = &fs::to_native(&entry.original_path);
+            let result = trash.remove(fsaccess, &entry);
+            report.record(
+                Action::DeleteTrashedPath,
+                &entry.original_path,
+                Outcome::from_result(&result),
+            );
+            match result {
+                Ok(()) => notifier.info("trash_empty", "Trashed path deleted", original_path, None),
+                Err(error) => notifier.error(
+                    "trash_empty",
+                    "Trashed path deletion failed",
+                    original_path,
+                    Some(Error::Io(error)),
+                ),
+            }
+        }
+
+        trash.remove_empty_trash_dirs(fsaccess).map_err(Error::Io)?;
+        report.sort();
+        Ok(report)
+    }
+}
+
+fn get_trash(ctx: &dyn Context) -> Trash {
+    let location = ctx.get_config().trash.clone().unwrap_or(TrashLocation::Xdg);
+    Trash::new(location)
+}
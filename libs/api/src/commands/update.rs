@@ -1,24 +1,36 @@
 use super::*;
 
+use fs::DeletePolicy;
 use rayon::prelude::*;
+use std::sync::Mutex;
 
 #[derive(PartialEq, Debug)]
 pub struct Update {
     pub create_hook: String,
     pub delete_hook: String,
     pub dry_run: bool,
+    pub git: bool,
     pub marker_text: String,
+    pub retry_changed: bool,
     pub root_dirs: PathList,
     pub substitute_variables: bool,
 }
 
+impl Default for Update {
+    fn default() -> Update {
+        Update::new()
+    }
+}
+
 impl Update {
     pub fn new() -> Update {
         Update {
             create_hook: String::new(),
             delete_hook: String::new(),
             dry_run: false,
+            git: false,
             marker_text: String::new(),
+            retry_changed: false,
             root_dirs: Config::default_root_dirs(),
             substitute_variables: true,
         }
@@ -26,23 +38,85 @@ impl Update {
 }
 
 impl Command for Update {
-    fn execute(&self, ctx: &Context) -> Result<()> {
-        let descr_list = ctx.crawl_dirs(&self.root_dirs);
+    fn execute(&self, ctx: &dyn Context) -> Result<Report> {
+        check_root_dirs(ctx, &self.root_dirs)?;
+
+        let mut report = Report::new();
+        let mut descr_list = crawl_dirs(ctx, &self.root_dirs, &mut report);
+        let report = Mutex::new(report);
+
+        self.update_markers(ctx, &descr_list, &report);
+
+        // Crawl directories changed in the meantime again and act on their current state.
+        if self.retry_changed {
+            let changed_dirs = report.lock().unwrap().take_changed_dirs();
+            if !changed_dirs.is_empty() {
+                let changed_descr_list = ctx.crawl_dirs_shallow(&changed_dirs);
+                self.update_markers(ctx, &changed_descr_list, &report);
+                descr_list.retain(|descr| !changed_dirs.contains(&descr.dir));
+                descr_list.extend(changed_descr_list);
+            }
+        }
+
+        let mut report = report.into_inner().unwrap();
+        write_manifests(ctx, &mut report);
+
+        // Stage created and unstage deleted markers.
+        if self.git {
+            ctx.update_git_index(
+                &self.root_dirs,
+                &report.get_done_paths(Action::CreateMarker),
+                &report.get_done_paths(Action::DeleteMarker),
+            );
+        }
+
+        report.sort();
+        Ok(report)
+    }
+
+    fn get_root_dirs(&self) -> Option<&PathList> {
+        Some(&self.root_dirs)
+    }
+}
+
+impl Update {
+    fn update_markers(
+        &self,
+        ctx: &dyn Context,
+        descr_list: &DirDescriptorList,
+        report: &Mutex<Report>,
+    ) {
+        let variables = Variables::new(ctx.get_config(), &self.root_dirs);
 
         // Delete markers.
         descr_list.par_iter().for_each(|descr| {
             if descr.has_marker() && descr.has_children() {
-                ctx.delete_marker(&descr.dir);
+                let outcome = if descr.get_directives().delete == DeletePolicy::Never {
+                    keep_marker(ctx, descr)
+                } else {
+                    ctx.delete_marker(descr, Some(DirStatus::Clashing))
+                };
+                report
+                    .lock()
+                    .unwrap()
+                    .record(Action::DeleteMarker, &descr.dir, outcome);
             }
         });
 
         // Create markers.
         descr_list.par_iter().for_each(|descr| {
             if !descr.has_marker() && !descr.has_children() {
-                ctx.create_marker(&descr.dir, &self.marker_text);
+                let outcome = if self.substitute_variables {
+                    let text = variables.substitute(&self.marker_text, &descr.dir);
+                    ctx.create_marker(descr, &text)
+                } else {
+                    ctx.create_marker(descr, &self.marker_text)
+                };
+                report
+                    .lock()
+                    .unwrap()
+                    .record(Action::CreateMarker, &descr.dir, outcome);
             }
         });
-
-        Ok(())
     }
 }
@@ -0,0 +1,103 @@
+use super::*;
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+use fs;
+use std::fmt::Display;
+use std::time::SystemTime;
+
+#[derive(Debug)]
+pub struct Variables {
+    pub date: SystemTime,
+    pub marker_name: String,
+    pub root_dirs: PathList,
+    pub utc: bool,
+}
+
+impl Variables {
+    pub fn new(cfg: &Config, root_dirs: &PathList) -> Variables {
+        Variables {
+            date: cfg.source_date_epoch.unwrap_or_else(SystemTime::now),
+            marker_name: cfg.marker_name.clone(),
+            // Crawled directories are canonical, so their roots have to be as well.
+            root_dirs: root_dirs
+                .iter()
+                .map(|dir| dir.canonicalize().unwrap_or_else(|_| dir.clone()))
+                .collect(),
+            // Reproducible dates must not depend on the local time zone.
+            utc: cfg.source_date_epoch.is_some(),
+        }
+    }
+
+    // Substitutes '${name}' references; unknown variables are kept as they are.
+    pub fn substitute(&self, text: &str, dir: &Path) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            result.push_str(&rest[..start]);
+            let reference = &rest[start..];
+            match reference.find('}') {
+                Some(end) => {
+                    match self.resolve(&reference[2..end], dir) {
+                        Some(value) => result.push_str(&value),
+                        None => result.push_str(&reference[..=end]),
+                    }
+                    rest = &reference[end + 1..];
+                }
+                None => {
+                    result.push_str(reference);
+                    rest = "";
+                }
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+
+    // Returns the directory relative to its innermost root, so that text does not depend on
+    // where the tree is checked out.
+    fn get_relative_dir(&self, dir: &Path) -> PathBuf {
+        let relative_dir = self
+            .root_dirs
+            .iter()
+            .filter_map(|root_dir| dir.strip_prefix(root_dir).ok())
+            .min_by_key(|relative_dir| relative_dir.components().count())
+            .unwrap_or(dir);
+        if relative_dir.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            relative_dir.to_owned()
+        }
+    }
+
+    fn resolve(&self, name: &str, dir: &Path) -> Option<String> {
+        if self.utc {
+            self.resolve_with_date(name, dir, &DateTime::<Utc>::from(self.date))
+        } else {
+            self.resolve_with_date(name, dir, &DateTime::<Local>::from(self.date))
+        }
+    }
+
+    fn resolve_with_date<Tz: TimeZone>(
+        &self,
+        name: &str,
+        dir: &Path,
+        date: &DateTime<Tz>,
+    ) -> Option<String>
+    where
+        Tz::Offset: Display,
+    {
+        match name {
+            "date" => Some(date.format("%Y-%m-%d").to_string()),
+            "datetime" => Some(date.to_rfc3339()),
+            "dir" => Some(fs::to_native(&self.get_relative_dir(dir))),
+            "dir.name" => dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            "linesep" => Some(if cfg!(windows) { "\r\n" } else { "\n" }.to_owned()),
+            "marker.name" => Some(self.marker_name.clone()),
+            "time" => Some(date.format("%H:%M:%S").to_string()),
+            _ if name.starts_with("env:") => std::env::var(&name[4..]).ok(),
+            _ => None,
+        }
+    }
+}
@@ -1,17 +1,45 @@
-use std::fs::File;
+use std::ffi::OsStr;
+use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
-use std::io::Result;
-use std::path::Path;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, Debug)]
+pub struct FileOptions {
+    pub mode: Option<u32>,
+    pub mtime: Option<SystemTime>,
+    pub owner: Option<(u32, u32)>,
+}
 
 pub trait FileSystemAccess: Sync + std::fmt::Debug {
-    fn create_file(&self, path: &Path, text: &str) -> Result<()>;
+    fn create_dir_all(&self, path: &Path) -> Result<()>;
+
+    // Fails if the file already exists.
+    fn create_file(&self, path: &Path, text: &str, options: &FileOptions) -> Result<()>;
+
+    // Atomically creates or replaces the file.
+    fn replace_file(&self, path: &Path, text: &str, options: &FileOptions) -> Result<()>;
+
+    fn move_path(&self, from: &Path, to: &Path) -> Result<()>;
+
+    fn remove_dir(&self, path: &Path) -> Result<()>;
 
     fn remove_file(&self, path: &Path) -> Result<()>;
 
     fn remove_dir_all(&self, path: &Path) -> Result<()>;
+
+    fn remove_xattr(&self, path: &Path, name: &str) -> Result<()>;
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()>;
+
+    fn set_times(&self, path: &Path, atime: SystemTime, mtime: SystemTime) -> Result<()>;
+
+    fn set_xattr(&self, path: &Path, name: &str, value: &str) -> Result<()>;
 }
 
-pub fn create_file_system_access(dry_run: bool) -> Box<FileSystemAccess> {
+pub fn create_file_system_access(dry_run: bool) -> Box<dyn FileSystemAccess> {
     if dry_run {
         Box::new(DryRunFileSystemAccess {})
     } else {
@@ -23,26 +51,272 @@ pub fn create_file_system_access(dry_run: bool) -> Box<FileSystemAccess> {
 struct RealFileSystemAccess {}
 
 impl FileSystemAccess for RealFileSystemAccess {
-    fn create_file(&self, path: &Path, text: &str) -> Result<()> {
-        let mut file = File::create(&path)?;
-        file.write_all(text.as_bytes())
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        std::fs::create_dir_all(path)
+    }
+
+    fn create_file(&self, path: &Path, text: &str, options: &FileOptions) -> Result<()> {
+        let temp_path = write_temp_file(path, text, options)?;
+        // Linking fails if the file has been created concurrently, so it is never clobbered.
+        let result = std::fs::hard_link(&temp_path, path);
+        let _ = std::fs::remove_file(&temp_path);
+        match result {
+            Err(ref error) if error.kind() != ErrorKind::AlreadyExists => {
+                // Fall back for file systems not supporting hard links.
+                let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+                let result = file
+                    .write_all(text.as_bytes())
+                    .and_then(|_| apply_file_options(&file, options));
+                if result.is_err() {
+                    let _ = std::fs::remove_file(path);
+                }
+                result
+            }
+            result => result,
+        }
+    }
+
+    fn replace_file(&self, path: &Path, text: &str, options: &FileOptions) -> Result<()> {
+        let temp_path = write_temp_file(path, text, options)?;
+        std::fs::rename(&temp_path, path).inspect_err(|_error| {
+            let _ = std::fs::remove_file(&temp_path);
+        })
+    }
+
+    fn move_path(&self, from: &Path, to: &Path) -> Result<()> {
+        match rename_no_replace(from, to) {
+            Err(ref error) if error.kind() == ErrorKind::CrossesDevices => {
+                copy_recursively(from, to)?;
+                if from.symlink_metadata()?.is_dir() {
+                    std::fs::remove_dir_all(from)
+                } else {
+                    std::fs::remove_file(from)
+                }
+            }
+            result => result,
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        std::fs::remove_dir(path)
     }
 
     fn remove_file(&self, path: &Path) -> Result<()> {
-        std::fs::remove_file(&path)
+        std::fs::remove_file(path)
     }
 
-    fn remove_dir_all(&self, _path: &Path) -> Result<()> {
-        // TODO std::fs::remove_dir_all(&path)
-        panic!("To be implemented!");
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        std::fs::remove_dir_all(path)
+    }
+
+    fn remove_xattr(&self, path: &Path, name: &str) -> Result<()> {
+        super::remove_xattr(path, name)
+    }
+
+    #[cfg(unix)]
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+    }
+
+    #[cfg(not(unix))]
+    fn set_permissions(&self, _path: &Path, _mode: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_times(&self, path: &Path, atime: SystemTime, mtime: SystemTime) -> Result<()> {
+        let times = std::fs::FileTimes::new()
+            .set_accessed(atime)
+            .set_modified(mtime);
+        File::open(path)?.set_times(times)
+    }
+
+    fn set_xattr(&self, path: &Path, name: &str, value: &str) -> Result<()> {
+        super::set_xattr(path, name, value.as_bytes())
+    }
+}
+
+fn write_temp_file(path: &Path, text: &str, options: &FileOptions) -> Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "path without file name"))?
+        .to_string_lossy()
+        .into_owned();
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        file_name,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp_path)?;
+    let result = file
+        .write_all(text.as_bytes())
+        .and_then(|_| apply_file_options(&file, options));
+    if let Err(error) = result {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(error);
+    }
+    Ok(temp_path)
+}
+
+// Tells whether the name is one of a temporary file written before creating or replacing a
+// file, like `.name.pid.counter.tmp`.
+pub fn is_temp_file_name(name: &OsStr) -> bool {
+    let is_number = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    let name = match name.to_str() {
+        Some(name) if name.starts_with('.') && name.ends_with(".tmp") => {
+            &name[1..name.len() - ".tmp".len()]
+        }
+        _ => return false,
+    };
+    let parts: Vec<_> = name.rsplitn(3, '.').collect();
+    parts.len() == 3 && is_number(parts[0]) && is_number(parts[1]) && !parts[2].is_empty()
+}
+
+fn apply_file_options(file: &File, options: &FileOptions) -> Result<()> {
+    set_owner_and_mode(file, options)?;
+    if let Some(mtime) = options.mtime {
+        file.set_modified(mtime)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_owner_and_mode(file: &File, options: &FileOptions) -> Result<()> {
+    use std::os::unix::fs::{fchown, PermissionsExt};
+    // Change owner first, since doing so may reset permission bits.
+    if let Some((uid, gid)) = options.owner {
+        fchown(file, Some(uid), Some(gid))?;
+    }
+    if let Some(mode) = options.mode {
+        file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_owner_and_mode(_file: &File, _options: &FileOptions) -> Result<()> {
+    Ok(())
+}
+
+// Plain renaming replaces existing files, which must never be lost. Checking for them
+// beforehand would race with their concurrent creation.
+#[cfg(target_os = "linux")]
+fn rename_no_replace(from: &Path, to: &Path) -> Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let to_c_string = |path: &Path| {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|error| Error::new(ErrorKind::InvalidInput, error))
+    };
+    let (c_from, c_to) = (to_c_string(from)?, to_c_string(to)?);
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            libc::AT_FDCWD,
+            c_from.as_ptr(),
+            libc::AT_FDCWD,
+            c_to.as_ptr(),
+            libc::RENAME_NOREPLACE,
+        )
+    };
+    if result == 0 {
+        return Ok(());
+    }
+    let error = Error::last_os_error();
+    match error.raw_os_error() {
+        // Neither the kernel nor every file system supports the flag.
+        Some(libc::EINVAL) | Some(libc::ENOSYS) => rename_by_link(from, to),
+        _ => Err(error),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn rename_no_replace(from: &Path, to: &Path) -> Result<()> {
+    rename_by_link(from, to)
+}
+
+// Linking fails if the target exists, like creating files does.
+fn rename_by_link(from: &Path, to: &Path) -> Result<()> {
+    if from.symlink_metadata()?.is_dir() {
+        // Directories cannot be linked, but renaming them only ever replaces empty ones.
+        return rename_if_missing(from, to);
+    }
+    match std::fs::hard_link(from, to) {
+        Ok(()) => std::fs::remove_file(from),
+        Err(ref error)
+            if error.kind() != ErrorKind::AlreadyExists
+                && error.kind() != ErrorKind::CrossesDevices =>
+        {
+            // Fall back for file systems not supporting hard links.
+            rename_if_missing(from, to)
+        }
+        Err(error) => Err(error),
     }
 }
 
+fn rename_if_missing(from: &Path, to: &Path) -> Result<()> {
+    if to.symlink_metadata().is_ok() {
+        return Err(Error::new(ErrorKind::AlreadyExists, "target path exists"));
+    }
+    std::fs::rename(from, to)
+}
+
+fn copy_recursively(from: &Path, to: &Path) -> Result<()> {
+    let metadata = from.symlink_metadata()?;
+    if metadata.file_type().is_symlink() {
+        copy_symlink(from, to)
+    } else if metadata.is_dir() {
+        std::fs::create_dir(to)?;
+        for entry in from.read_dir()? {
+            let entry = entry?;
+            copy_recursively(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        let mut target = OpenOptions::new().write(true).create_new(true).open(to)?;
+        std::io::copy(&mut File::open(from)?, &mut target)?;
+        target.set_permissions(metadata.permissions())
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> Result<()> {
+    std::fs::copy(&from, &to).map(|_| ())
+}
+
 #[derive(Debug)]
 struct DryRunFileSystemAccess {}
 
 impl FileSystemAccess for DryRunFileSystemAccess {
-    fn create_file(&self, _path: &Path, _text: &str) -> Result<()> {
+    fn create_dir_all(&self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    fn create_file(&self, _path: &Path, _text: &str, _options: &FileOptions) -> Result<()> {
+        Ok(())
+    }
+
+    fn replace_file(&self, _path: &Path, _text: &str, _options: &FileOptions) -> Result<()> {
+        Ok(())
+    }
+
+    fn move_path(&self, _from: &Path, _to: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    fn remove_dir(&self, _path: &Path) -> Result<()> {
         Ok(())
     }
 
@@ -53,4 +327,20 @@ impl FileSystemAccess for DryRunFileSystemAccess {
     fn remove_dir_all(&self, _path: &Path) -> Result<()> {
         Ok(())
     }
+
+    fn remove_xattr(&self, _path: &Path, _name: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_permissions(&self, _path: &Path, _mode: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_times(&self, _path: &Path, _atime: SystemTime, _mtime: SystemTime) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_xattr(&self, _path: &Path, _name: &str, _value: &str) -> Result<()> {
+        Ok(())
+    }
 }
@@ -1,6 +1,8 @@
 use super::*;
+use git::GitRepository;
 use rayon::prelude::*;
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 pub type DirEntryList = Vec<DirEntry>;
 
@@ -9,7 +11,11 @@ pub struct DirDescriptor {
     pub dir: PathBuf,
     pub children: DirEntryList,
 
+    directives: Directives,
+    ignored_child_indexes: HashSet<usize>,
+    listed_in_manifest: bool,
     marker_file_child_index: Option<usize>,
+    marker_xattr: bool,
     subdir_child_indexes: HashSet<usize>,
 }
 
@@ -45,15 +51,34 @@ impl DirDescriptor {
             .for_each(f)
     }
 
+    pub fn get_directives(&self) -> &Directives {
+        &self.directives
+    }
+
     pub fn get_child_count(&self) -> usize {
-        let mut count = self.children.len();
-        if self.has_marker() {
+        let mut count = self.children.len() - self.ignored_child_indexes.len();
+        if self.marker_file_child_index.is_some() {
             assert!(count > 0);
             count -= 1;
         }
         count
     }
 
+    // Returns the kinds of markers found, since converting markers may leave several.
+    pub fn get_marker_backends(&self) -> Vec<MarkerStrategy> {
+        let mut backends = vec![];
+        if self.has_marker_file() {
+            backends.push(MarkerStrategy::File);
+        }
+        if self.listed_in_manifest {
+            backends.push(MarkerStrategy::Manifest);
+        }
+        if self.marker_xattr {
+            backends.push(MarkerStrategy::Xattr);
+        }
+        backends
+    }
+
     pub fn get_marker_direntry(&self) -> Option<&DirEntry> {
         if let Some(index) = self.marker_file_child_index {
             Some(&self.children[index])
@@ -82,85 +107,218 @@ impl DirDescriptor {
     }
 
     pub fn has_marker(&self) -> bool {
+        self.marker_file_child_index.is_some() || self.listed_in_manifest || self.marker_xattr
+    }
+
+    pub fn has_marker_file(&self) -> bool {
         self.marker_file_child_index.is_some()
     }
+
+    pub fn has_marker_xattr(&self) -> bool {
+        self.marker_xattr
+    }
 }
 
 pub type DirDescriptorList = Vec<DirDescriptor>;
 pub type DirDescriptorMap = HashMap<PathBuf, DirDescriptor>;
 
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Emptiness {
+    FileSystem,
+    GitIndex,
+    GitHead,
+}
+
+#[derive(Default, Debug)]
+struct GitContent {
+    ignored_dirs: HashSet<PathBuf>,
+    tracked_files: HashSet<PathBuf>,
+}
+
 #[derive(Debug)]
 pub struct FileSystemCrawler {
     pub exclude_dirs: PathList,
     pub dereference_symlinks: bool,
+    pub emptiness: Emptiness,
+    pub manifest_dirs: HashSet<PathBuf>,
+    pub manifest_name: String,
     pub marker_name: String,
+    pub marker_strategy: MarkerStrategy,
+    // Looking for marker attributes costs a system call per directory.
+    pub read_marker_xattrs: bool,
+    pub read_throttle: Arc<Throttle>,
+    pub retry_log: Arc<RetryLog>,
+    pub retry_policy: RetryPolicy,
 }
 
 impl FileSystemCrawler {
-    fn crawl_dir(&self, mut dir: PathBuf) -> Option<DirDescriptor> {
-        dir = match dir.canonicalize() {
-            Ok(dir) => dir,
-            Err(error) => {
-                warn!(target: "FileSystemCrawler", "{}", error);
-                return None;
+    fn collect_git_content(&self, root_dirs: &PathList) -> Option<GitContent> {
+        if self.emptiness == Emptiness::FileSystem {
+            return None;
+        }
+
+        let mut content = GitContent::default();
+        let mut repos: Vec<GitRepository> = vec![];
+        for root_dir in root_dirs {
+            let root_dir = match root_dir.canonicalize() {
+                Ok(root_dir) => root_dir,
+                Err(error) => {
+                    warn!(target: "FileSystemCrawler", "{}", error);
+                    continue;
+                }
+            };
+            if repos.iter().any(|repo| repo.contains(&root_dir)) {
+                continue;
             }
-        };
+            let repo = match GitRepository::discover(&root_dir) {
+                Ok(repo) => repo,
+                Err(error) => {
+                    warn!(target: "FileSystemCrawler", "{}", error);
+                    continue;
+                }
+            };
+
+            let tracked_files = match self.emptiness {
+                Emptiness::GitHead => repo.head_files(),
+                _ => repo.index_files(),
+            };
+            match tracked_files.and_then(|files| Ok((files, repo.ignored_dirs()?))) {
+                Ok((tracked_files, ignored_dirs)) => {
+                    content.tracked_files.extend(tracked_files);
+                    content.ignored_dirs.extend(ignored_dirs);
+                }
+                Err(error) => warn!(target: "FileSystemCrawler", "{}", error),
+            }
+            repos.push(repo);
+        }
+        Some(content)
+    }
 
-        let entries = match dir.read_dir() {
-            Ok(entries) => entries,
+    fn crawl_dir(
+        &self,
+        mut dir: PathBuf,
+        git_content: Option<&GitContent>,
+    ) -> Option<DirDescriptor> {
+        let result = self
+            .retry_policy
+            .run(&self.retry_log, "crawl_dir", &dir, || {
+                self.read_throttle.run(|| {
+                    let dir = dir.canonicalize()?;
+                    let mut children = DirEntryList::new();
+                    for entry in dir.read_dir()? {
+                        match entry {
+                            Ok(entry) => children.push(entry),
+                            // Read the whole directory again rather than missing an entry.
+                            Err(ref error) if TransientError::of(error).is_some() => {
+                                return Err(std::io::Error::new(error.kind(), error.to_string()))
+                            }
+                            Err(error) => warn!(target: "FileSystemCrawler", "{}", error),
+                        }
+                    }
+                    Ok((dir, children))
+                })
+            });
+        let children = match result {
+            Ok((canonical_dir, children)) => {
+                dir = canonical_dir;
+                children
+            }
             Err(error) => {
                 warn!(target: "FileSystemCrawler", "{}", error);
                 return None;
             }
         };
-        let children: DirEntryList = entries
-            .filter_map(|entry| match entry {
-                Ok(entry) => Some(entry),
-                Err(error) => {
-                    warn!(target: "FileSystemCrawler", "{}", error);
-                    None
-                }
-            })
-            .collect();
 
+        let mut ignored_child_indexes = HashSet::new();
         let mut marker_file_child_index = None;
         let mut subdir_child_indexes = HashSet::new();
         children.iter().enumerate().for_each(|(index, entry)| {
-            if self.is_crawlable_dir(&entry.path()) {
-                subdir_child_indexes.insert(index);
-            } else if marker_file_child_index.is_none() {
-                let entry_file_name = entry.file_name(); // Make temporary live long enough.
-                let entry_file_name = entry_file_name.to_str().unwrap_or("");
-                if self.marker_name == entry_file_name {
-                    let entry_is_file = entry
-                        .file_type()
-                        .and_then(|ft| Ok(ft.is_file()))
-                        .unwrap_or_else(|error| {
-                            warn!(target: "FileSystemCrawler", "{}", &error);
-                            false
-                        });
-
-                    if entry_is_file {
-                        marker_file_child_index = Some(index)
-                    }
+            let path = &entry.path();
+            if self.is_crawlable_dir(path) {
+                // Ignored directories are neither content nor crawled.
+                if git_content.is_some_and(|content| content.ignored_dirs.contains(path)) {
+                    ignored_child_indexes.insert(index);
+                } else {
+                    subdir_child_indexes.insert(index);
                 }
+            } else if is_temp_file_name(&entry.file_name()) {
+                // Temporary files of concurrent runs are neither content nor markers.
+                ignored_child_indexes.insert(index);
+            } else if self.marker_strategy == MarkerStrategy::Manifest {
+                // Manifests are no content, just like marker files.
+                if self.is_file_named(entry, &self.manifest_name)
+                    || git_content.is_some_and(|content| !content.tracked_files.contains(path))
+                {
+                    ignored_child_indexes.insert(index);
+                }
+            } else if marker_file_child_index.is_none()
+                && self.is_file_named(entry, &self.marker_name)
+            {
+                marker_file_child_index = Some(index)
+            } else if git_content.is_some_and(|content| !content.tracked_files.contains(path)) {
+                ignored_child_indexes.insert(index);
             }
         });
 
+        let listed_in_manifest = self.manifest_dirs.contains(&dir);
+        let marker_xattr = if self.read_marker_xattrs {
+            self.read_throttle
+                .run(|| get_xattr(&dir, MARKER_XATTR_NAME))
+                .unwrap_or_else(|error| {
+                    warn!(target: "FileSystemCrawler", "{}", &error);
+                    None
+                })
+        } else {
+            None
+        };
+
+        // Directives are taken from the marker file or else the marker attribute.
+        let header = match (marker_file_child_index, &marker_xattr) {
+            (Some(index), _) => read_header_line(&children[index].path()).ok(),
+            (None, Some(value)) => Some(String::from_utf8_lossy(value).into_owned()),
+            (None, None) => None,
+        };
+        let directives = header
+            .map(|header| {
+                Directives::parse(&header).unwrap_or_else(|error| {
+                    warn!(target: "FileSystemCrawler", "{}: {}", to_native(&dir), error);
+                    Directives::new()
+                })
+            })
+            .unwrap_or_else(Directives::new);
         Some(DirDescriptor {
-            dir: dir,
-            children: children,
-            marker_file_child_index: marker_file_child_index,
-            subdir_child_indexes: subdir_child_indexes,
+            dir,
+            children,
+            directives,
+            ignored_child_indexes,
+            listed_in_manifest,
+            marker_file_child_index,
+            marker_xattr: marker_xattr.is_some(),
+            subdir_child_indexes,
         })
     }
 
+    pub fn crawl_dirs_shallow(&self, mut dirs: PathList) -> DirDescriptorMap {
+        dirs.retain(|dir| self.is_crawlable_dir(dir));
+
+        let git_content = self.collect_git_content(&dirs);
+        let git_content = git_content.as_ref();
+
+        dirs.into_par_iter()
+            .filter_map(|dir| self.crawl_dir(dir, git_content))
+            .map(|descr| (descr.dir.clone(), descr))
+            .collect()
+    }
+
     pub fn crawl_dirs(&self, mut dirs_to_visit: PathList) -> DirDescriptorMap {
         dirs_to_visit = dirs_to_visit
             .into_iter()
             .filter(|dir| self.is_crawlable_dir(&dir))
             .collect();
 
+        let git_content = self.collect_git_content(&dirs_to_visit);
+        let git_content = git_content.as_ref();
+
         let mut descr_map = DirDescriptorMap::new();
 
         while !dirs_to_visit.is_empty() {
@@ -169,7 +327,7 @@ impl FileSystemCrawler {
                 .filter_map(|dir| {
                     let dir_already_crawled = descr_map.contains_key(&dir);
                     if !dir_already_crawled {
-                        self.crawl_dir(dir)
+                        self.crawl_dir(dir, git_content)
                     } else {
                         None
                     }
@@ -204,6 +362,65 @@ impl FileSystemCrawler {
         descr_map
     }
 
+    // Re-reads a crawled directory, telling whether it has a marker and children now. Git
+    // is not consulted again: entries added since crawling are untracked and hence only
+    // count as content if the file system tells emptiness.
+    pub fn get_current_state(&self, descr: &DirDescriptor) -> std::io::Result<(bool, bool)> {
+        let crawled_indexes: HashMap<_, _> = descr
+            .children
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (entry.file_name(), index))
+            .collect();
+
+        let mut has_marker_file = false;
+        let mut has_children = false;
+        let entries = self
+            .read_throttle
+            .run(|| descr.dir.read_dir()?.collect::<std::io::Result<Vec<_>>>())?;
+        for entry in entries {
+            match crawled_indexes.get(&entry.file_name()) {
+                Some(index) if descr.marker_file_child_index == Some(*index) => {
+                    has_marker_file = true
+                }
+                Some(index) => has_children |= !descr.ignored_child_indexes.contains(index),
+                None if is_temp_file_name(&entry.file_name()) => {}
+                None if self.marker_strategy == MarkerStrategy::Manifest => {
+                    has_children |= self.emptiness == Emptiness::FileSystem
+                        && !self.is_file_named(&entry, &self.manifest_name)
+                }
+                None if self.is_file_named(&entry, &self.marker_name) => has_marker_file = true,
+                None => has_children |= self.emptiness == Emptiness::FileSystem,
+            }
+        }
+
+        let has_marker_xattr = self.read_marker_xattrs
+            && self
+                .read_throttle
+                .run(|| get_xattr(&descr.dir, MARKER_XATTR_NAME))?
+                .is_some();
+        Ok((
+            has_marker_file || has_marker_xattr || descr.listed_in_manifest,
+            has_children,
+        ))
+    }
+
+    fn is_file_named(&self, entry: &DirEntry, name: &str) -> bool {
+        let entry_file_name = entry.file_name(); // Make temporary live long enough.
+        let entry_file_name = entry_file_name.to_str().unwrap_or("");
+        if name != entry_file_name {
+            return false;
+        }
+
+        entry
+            .file_type()
+            .map(|ft| ft.is_file())
+            .unwrap_or_else(|error| {
+                warn!(target: "FileSystemCrawler", "{}", &error);
+                false
+            })
+    }
+
     fn is_crawlable_dir(&self, dir: &PathBuf) -> bool {
         if !dir.is_dir() {
             return false;
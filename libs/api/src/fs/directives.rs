@@ -0,0 +1,108 @@
+use super::*;
+use std::ffi::OsStr;
+
+pub const DIRECTIVES_PREFIX: &str = "markemptydirs:";
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DeletePolicy {
+    // Markers are deleted when their directory is no longer empty or on clean.
+    Auto,
+    Never,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PurgePolicy {
+    Always,
+    Never,
+    // Content is purged on clean as well.
+    OnClean,
+}
+
+// Per-directory policy declared in the first line of a marker's text, e.g.
+// "markemptydirs: delete=never purge=on-clean keep=README.md,.gitignore".
+#[derive(Clone, PartialEq, Debug)]
+pub struct Directives {
+    pub delete: DeletePolicy,
+    pub keep: Vec<String>,
+    pub purge: PurgePolicy,
+    // Tool name and version of markers created by markemptydirs, e.g. "markemptydirs/0.1".
+    pub signature: Option<String>,
+}
+
+impl Default for Directives {
+    fn default() -> Directives {
+        Directives::new()
+    }
+}
+
+impl Directives {
+    pub fn new() -> Directives {
+        Directives {
+            delete: DeletePolicy::Auto,
+            keep: vec![],
+            purge: PurgePolicy::Always,
+            signature: None,
+        }
+    }
+
+    // Text without directives header results in default directives.
+    pub fn parse(text: &str) -> std::result::Result<Directives, String> {
+        let mut directives = Directives::new();
+        let header = match text.lines().next() {
+            Some(line) if line.trim_start().starts_with(DIRECTIVES_PREFIX) => {
+                &line.trim_start()[DIRECTIVES_PREFIX.len()..]
+            }
+            _ => return Ok(directives),
+        };
+
+        for directive in header.split_whitespace() {
+            let mut parts = directive.splitn(2, '=');
+            match (parts.next().unwrap_or(""), parts.next()) {
+                ("delete", Some("auto")) => directives.delete = DeletePolicy::Auto,
+                ("delete", Some("never")) => directives.delete = DeletePolicy::Never,
+                ("keep", Some(names)) => directives.keep.extend(
+                    names
+                        .split(',')
+                        .filter(|name| !name.is_empty())
+                        .map(|name| name.to_owned()),
+                ),
+                ("purge", Some("always")) => directives.purge = PurgePolicy::Always,
+                ("purge", Some("never")) => directives.purge = PurgePolicy::Never,
+                ("purge", Some("on-clean")) => directives.purge = PurgePolicy::OnClean,
+                ("signature", Some(signature)) => directives.signature = Some(signature.to_owned()),
+                _ => return Err(format!("invalid directive '{}'", directive)),
+            }
+        }
+        Ok(directives)
+    }
+
+    pub fn keeps(&self, name: &OsStr) -> bool {
+        self.keep.iter().any(|keep| OsStr::new(keep) == name)
+    }
+
+    pub fn is_signed_by(&self, tool_name: &str) -> bool {
+        match self.signature {
+            Some(ref signature) => signature.split('/').next() == Some(tool_name),
+            None => false,
+        }
+    }
+}
+
+// Adds the signature to the directives header of the text, creating one if necessary.
+pub fn sign_marker_text(text: &str, signature: &str) -> String {
+    let directive = format!("signature={}", signature);
+    let trimmed_text = text.trim_start();
+    if let Some(rest) = trimmed_text.strip_prefix(DIRECTIVES_PREFIX) {
+        format!("{} {}{}", DIRECTIVES_PREFIX, directive, rest)
+    } else {
+        format!("{} {}\n{}", DIRECTIVES_PREFIX, directive, text)
+    }
+}
+
+// Reads the line of a marker file possibly holding directives.
+pub fn read_header_line(marker_file: &Path) -> std::io::Result<String> {
+    use std::io::BufRead;
+    let mut line = String::new();
+    std::io::BufReader::new(std::fs::File::open(marker_file)?).read_line(&mut line)?;
+    Ok(line)
+}
@@ -1,4 +1,5 @@
 use pathdiff::diff_paths;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
 pub fn get_absolute_dir(dir: &PathBuf) -> std::io::Result<PathBuf> {
@@ -10,12 +11,40 @@ pub fn get_absolute_dir(dir: &PathBuf) -> std::io::Result<PathBuf> {
     Ok(abs_dir)
 }
 
+// Returns the number of files and their total size in bytes. Symlinks are not followed.
+pub fn get_disk_usage(path: &Path) -> std::io::Result<(usize, u64)> {
+    let metadata = path.symlink_metadata()?;
+    if !metadata.is_dir() {
+        return Ok((1, metadata.len()));
+    }
+
+    let mut usage = (0, 0);
+    for entry in path.read_dir()? {
+        let (file_count, size) = get_disk_usage(&entry?.path())?;
+        usage.0 += file_count;
+        usage.1 += size;
+    }
+    Ok(usage)
+}
+
+#[cfg(unix)]
+pub fn get_owner(path: &Path) -> std::io::Result<Option<(u32, u32)>> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = path.metadata()?;
+    Ok(Some((metadata.uid(), metadata.gid())))
+}
+
+#[cfg(not(unix))]
+pub fn get_owner(_path: &Path) -> std::io::Result<Option<(u32, u32)>> {
+    Ok(None)
+}
+
 pub fn get_relative_dir(dir: &PathBuf, base_dir: &PathBuf) -> Option<PathBuf> {
     diff_paths(dir, base_dir)
 }
 
 pub fn get_relative_dir_to_current_dir(dir: &PathBuf) -> std::io::Result<Option<PathBuf>> {
-    let ref cur_dir = std::env::current_dir()?;
+    let cur_dir = &std::env::current_dir()?;
     match get_relative_dir(dir, cur_dir) {
         Some(dir) => {
             let rel_dir = Path::new(".");
@@ -29,6 +58,29 @@ pub fn get_relative_dir_to_current_dir(dir: &PathBuf) -> std::io::Result<Option<
     }
 }
 
+pub fn read_path_list(source: &Path, null_separated: bool) -> std::io::Result<Vec<PathBuf>> {
+    let mut text = String::new();
+    if source == Path::new("-") {
+        std::io::stdin().read_to_string(&mut text)?;
+    } else {
+        std::fs::File::open(source)?.read_to_string(&mut text)?;
+    }
+
+    let separator = if null_separated { '\0' } else { '\n' };
+    Ok(text
+        .split(separator)
+        .map(|line| {
+            if null_separated {
+                line
+            } else {
+                line.trim_end_matches('\r')
+            }
+        })
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
 pub fn to_native(dir: &PathBuf) -> String {
     dir.as_os_str().to_string_lossy().into_owned()
 }
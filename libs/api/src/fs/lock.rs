@@ -0,0 +1,206 @@
+use super::*;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result, SeekFrom};
+use std::time::{Duration, Instant};
+
+// Advisory lock preventing concurrent runs on the same or nested root directories. The
+// lock files live outside of the root directory, so that they never count as directory
+// content.
+#[derive(Debug)]
+pub struct RootLock {
+    // Files locked for the ancestors of the root directory and the root directory itself.
+    files: Vec<File>,
+    // Path of the file created in case the file system does not support locking.
+    pid_file: Option<PathBuf>,
+}
+
+impl RootLock {
+    // Waits up to the timeout, calling back with a description of the lock holder once.
+    // Ancestors are locked shared, so that runs on siblings do not exclude each other while
+    // ones on an ancestor, which lock it exclusively, do.
+    pub fn acquire<F>(root_dir: &Path, timeout: Duration, mut on_wait: F) -> Result<RootLock>
+    where
+        F: FnMut(&str),
+    {
+        let root_dir = root_dir.canonicalize()?;
+        let deadline = Instant::now() + timeout;
+        let mut waiting = false;
+        let mut lock = RootLock {
+            files: vec![],
+            pid_file: None,
+        };
+
+        let mut ancestors: Vec<_> = root_dir.ancestors().skip(1).collect();
+        ancestors.reverse();
+        for dir in ancestors {
+            if let Some((file, _)) = lock_dir(dir, true, deadline, &mut waiting, &mut on_wait)? {
+                lock.files.push(file);
+            }
+        }
+        if let Some((file, pid_file)) =
+            lock_dir(&root_dir, false, deadline, &mut waiting, &mut on_wait)?
+        {
+            lock.files.push(file);
+            lock.pid_file = pid_file;
+        }
+        Ok(lock)
+    }
+}
+
+impl Drop for RootLock {
+    fn drop(&mut self) {
+        if let Some(ref pid_file) = self.pid_file {
+            let _ = std::fs::remove_file(pid_file);
+        }
+        // The root directory's file is locked last and exclusively, so clear its process ID
+        // for the next holder. Waiting for shared holders then names none.
+        if let Some(file) = self.files.last() {
+            let _ = file.set_len(0);
+        }
+        for file in &self.files {
+            let _ = file.unlock();
+        }
+    }
+}
+
+// Returns the locked file, along with the process ID file created instead of locking if
+// the file system does not support it. Process ID files cannot be shared, so shared locks
+// are left out then.
+fn lock_dir<F>(
+    dir: &Path,
+    shared: bool,
+    deadline: Instant,
+    waiting: &mut bool,
+    on_wait: &mut F,
+) -> Result<Option<(File, Option<PathBuf>)>>
+where
+    F: FnMut(&str),
+{
+    let lock_file = get_lock_file(dir)?;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_file)?;
+
+    loop {
+        let result = if shared {
+            file.try_lock_shared()
+        } else {
+            file.try_lock()
+        };
+        let holder = match result {
+            Ok(()) => {
+                // Only the exclusive holder names itself, replacing the process ID left by
+                // a previous one, as shared holders would overwrite each other.
+                if !shared {
+                    file.set_len(0)?;
+                    file.seek(SeekFrom::Start(0))?;
+                    writeln!(file, "{}", std::process::id())?;
+                }
+                return Ok(Some((file, None)));
+            }
+            Err(TryLockError::WouldBlock) => read_pid(&lock_file),
+            Err(TryLockError::Error(ref error)) if error.kind() == ErrorKind::Unsupported => {
+                if shared {
+                    return Ok(None);
+                }
+                match create_pid_file(&lock_file)? {
+                    Ok(pid_file) => return Ok(Some((file, Some(pid_file)))),
+                    Err(holder) => holder,
+                }
+            }
+            Err(TryLockError::Error(error)) => return Err(error),
+        };
+
+        if Instant::now() >= deadline {
+            return Err(Error::new(
+                ErrorKind::WouldBlock,
+                format!("locked by {}", describe_process(holder)),
+            ));
+        }
+        if !*waiting {
+            on_wait(&describe_process(holder));
+            *waiting = true;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+// Lock files are named after a hash of the canonical root directory, so that all ways of
+// naming the directory share the lock.
+fn get_lock_file(root_dir: &Path) -> Result<PathBuf> {
+    let root_dir = root_dir.canonicalize()?;
+    let lock_dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(ref runtime_dir) if !runtime_dir.is_empty() => {
+            PathBuf::from(runtime_dir).join("markemptydirs")
+        }
+        _ => std::env::temp_dir().join("markemptydirs-locks"),
+    };
+    std::fs::create_dir_all(&lock_dir)?;
+
+    // FNV-1a, which unlike the standard library's hasher is stable across releases.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in to_native(&root_dir).bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    Ok(lock_dir.join(format!("{:016x}.lock", hash)))
+}
+
+// Falls back to exclusively creating a file holding the process ID. A file left by a
+// process that no longer exists is stale and replaced.
+fn create_pid_file(lock_file: &Path) -> Result<std::result::Result<PathBuf, Option<u32>>> {
+    let pid_file = lock_file.with_extension("pid");
+    loop {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&pid_file)
+        {
+            Ok(mut file) => {
+                writeln!(file, "{}", std::process::id())?;
+                return Ok(Ok(pid_file));
+            }
+            Err(ref error) if error.kind() == ErrorKind::AlreadyExists => {
+                let holder = read_pid(&pid_file);
+                match holder {
+                    Some(pid) if !is_process_alive(pid) => {
+                        std::fs::remove_file(&pid_file)?;
+                    }
+                    _ => return Ok(Err(holder)),
+                }
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+fn read_pid(file: &Path) -> Option<u32> {
+    std::fs::read_to_string(file).ok()?.trim().parse().ok()
+}
+
+fn describe_process(pid: Option<u32>) -> String {
+    let pid = match pid {
+        Some(pid) => pid,
+        None => return "another process".to_owned(),
+    };
+    // Name the program as well where the proc file system tells it.
+    match std::fs::read_to_string(format!("/proc/{}/comm", pid)) {
+        Ok(ref name) if !name.trim().is_empty() => format!("process {} ({})", pid, name.trim()),
+        _ => format!("process {}", pid),
+    }
+}
+
+#[cfg(unix)]
+fn is_process_alive(pid: u32) -> bool {
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    result == 0 || Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_process_alive(_pid: u32) -> bool {
+    true
+}
@@ -0,0 +1,166 @@
+use super::*;
+use std::collections::BTreeSet;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Component;
+
+const MANIFEST_HEADER: &str = "# Empty directories recorded by markemptydirs";
+
+#[derive(Copy, Clone, PartialEq, Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkerStrategy {
+    // A marker file in every empty directory.
+    File,
+    // A single manifest per root directory listing all empty directories.
+    Manifest,
+    // An extended attribute set on every empty directory.
+    Xattr,
+}
+
+// The manifest lists empty directories relative to its root directory, one per line,
+// using '/' as separator. Empty lines and lines starting with '#' are ignored.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Manifest {
+    pub path: PathBuf,
+    pub root_dir: PathBuf,
+    dirs: BTreeSet<PathBuf>,
+    changed: bool,
+}
+
+impl Manifest {
+    pub fn new(root_dir: &Path, name: &str) -> Manifest {
+        Manifest {
+            path: root_dir.join(name),
+            root_dir: root_dir.to_owned(),
+            dirs: BTreeSet::new(),
+            changed: false,
+        }
+    }
+
+    // A missing manifest file is read as an empty manifest.
+    pub fn read(root_dir: &Path, name: &str) -> Result<Manifest> {
+        let mut manifest = Manifest::new(root_dir, name);
+        let text = match std::fs::read_to_string(&manifest.path) {
+            Ok(text) => text,
+            Err(ref error) if error.kind() == ErrorKind::NotFound => return Ok(manifest),
+            Err(error) => return Err(error),
+        };
+
+        for line in text.lines().map(|line| line.trim_end_matches('\r')) {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let rel_dir = PathBuf::from(line);
+            // Entries must not point outside of the root directory.
+            if rel_dir
+                .components()
+                .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+            {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid manifest entry '{}'", line),
+                ));
+            }
+            // Normalize, so that '.' denotes the root directory itself.
+            manifest.dirs.insert(
+                rel_dir
+                    .components()
+                    .filter(|component| component != &Component::CurDir)
+                    .collect(),
+            );
+        }
+        Ok(manifest)
+    }
+
+    pub fn contains(&self, dir: &Path) -> bool {
+        self.get_relative_dir(dir)
+            .is_some_and(|rel_dir| self.dirs.contains(&rel_dir))
+    }
+
+    pub fn covers(&self, dir: &Path) -> bool {
+        dir.starts_with(&self.root_dir)
+    }
+
+    pub fn get_dirs(&self) -> PathList {
+        self.dirs
+            .iter()
+            .map(|rel_dir| self.root_dir.join(rel_dir))
+            .collect()
+    }
+
+    pub fn insert(&mut self, dir: &Path) -> Result<bool> {
+        let rel_dir = self.get_relative_dir_checked(dir)?;
+        let inserted = self.dirs.insert(rel_dir);
+        self.changed |= inserted;
+        Ok(inserted)
+    }
+
+    pub fn remove(&mut self, dir: &Path) -> Result<bool> {
+        let rel_dir = self.get_relative_dir_checked(dir)?;
+        let removed = self.dirs.remove(&rel_dir);
+        self.changed |= removed;
+        Ok(removed)
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dirs.is_empty()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n", MANIFEST_HEADER);
+        for rel_dir in &self.dirs {
+            let components: Vec<_> = rel_dir
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            if components.is_empty() {
+                text.push_str(".\n");
+            } else {
+                text.push_str(&components.join("/"));
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    // An empty manifest is deleted rather than written.
+    pub fn write(&mut self, fsaccess: &dyn FileSystemAccess) -> Result<()> {
+        if self.is_empty() {
+            if self.path.is_file() {
+                fsaccess.remove_file(&self.path)?;
+            }
+        } else {
+            fsaccess.replace_file(&self.path, &self.to_text(), &FileOptions::default())?;
+        }
+        self.changed = false;
+        Ok(())
+    }
+
+    fn get_relative_dir(&self, dir: &Path) -> Option<PathBuf> {
+        dir.strip_prefix(&self.root_dir)
+            .ok()
+            .map(|rel_dir| rel_dir.to_owned())
+    }
+
+    fn get_relative_dir_checked(&self, dir: &Path) -> Result<PathBuf> {
+        self.get_relative_dir(dir).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "directory outside of manifest root directory",
+            )
+        })
+    }
+}
+
+// Returns the index of the innermost manifest covering the directory.
+pub fn find_manifest(manifests: &[Manifest], dir: &Path) -> Option<usize> {
+    manifests
+        .iter()
+        .enumerate()
+        .filter(|(_, manifest)| manifest.covers(dir))
+        .max_by_key(|(_, manifest)| manifest.root_dir.components().count())
+        .map(|(index, _)| index)
+}
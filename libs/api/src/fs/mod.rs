@@ -9,5 +9,32 @@ pub use self::access::*;
 mod crawling;
 pub use self::crawling::*;
 
+mod directives;
+pub use self::directives::*;
+
 mod helpers;
 pub use self::helpers::*;
+
+mod lock;
+pub use self::lock::*;
+
+mod manifest;
+pub use self::manifest::*;
+
+mod plan;
+pub use self::plan::*;
+
+mod retry;
+pub use self::retry::*;
+
+mod safety;
+pub use self::safety::*;
+
+mod throttle;
+pub use self::throttle::*;
+
+mod trash;
+pub use self::trash::*;
+
+mod xattr;
+pub use self::xattr::*;
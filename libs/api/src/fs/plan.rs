@@ -0,0 +1,265 @@
+use super::*;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlannedOperation {
+    CreateDirAll {
+        path: PathBuf,
+    },
+    CreateFile {
+        path: PathBuf,
+        text: String,
+        options: FileOptions,
+    },
+    MovePath {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    RemoveDir {
+        path: PathBuf,
+    },
+    RemoveDirAll {
+        path: PathBuf,
+    },
+    RemoveFile {
+        path: PathBuf,
+    },
+    RemoveXattr {
+        path: PathBuf,
+        name: String,
+    },
+    ReplaceFile {
+        path: PathBuf,
+        text: String,
+        options: FileOptions,
+    },
+    SetPermissions {
+        path: PathBuf,
+        mode: u32,
+    },
+    SetTimes {
+        path: PathBuf,
+        atime: SystemTime,
+        mtime: SystemTime,
+    },
+    SetXattr {
+        path: PathBuf,
+        name: String,
+        value: String,
+    },
+}
+
+impl PlannedOperation {
+    pub fn get_path(&self) -> &PathBuf {
+        match *self {
+            PlannedOperation::CreateDirAll { ref path } => path,
+            PlannedOperation::CreateFile { ref path, .. } => path,
+            PlannedOperation::MovePath { ref from, .. } => from,
+            PlannedOperation::RemoveDir { ref path } => path,
+            PlannedOperation::RemoveDirAll { ref path } => path,
+            PlannedOperation::RemoveFile { ref path } => path,
+            PlannedOperation::RemoveXattr { ref path, .. } => path,
+            PlannedOperation::ReplaceFile { ref path, .. } => path,
+            PlannedOperation::SetPermissions { ref path, .. } => path,
+            PlannedOperation::SetTimes { ref path, .. } => path,
+            PlannedOperation::SetXattr { ref path, .. } => path,
+        }
+    }
+
+    // Returns the existing path the operation removes or modifies, if any. Creating paths
+    // never clobbers existing ones.
+    pub fn get_modified_path(&self) -> Option<&PathBuf> {
+        match *self {
+            PlannedOperation::CreateDirAll { .. } | PlannedOperation::CreateFile { .. } => None,
+            _ => Some(self.get_path()),
+        }
+    }
+
+    pub fn apply(&self, fsaccess: &dyn FileSystemAccess) -> Result<()> {
+        match *self {
+            PlannedOperation::CreateDirAll { ref path } => fsaccess.create_dir_all(path),
+            PlannedOperation::CreateFile {
+                ref path,
+                ref text,
+                ref options,
+            } => fsaccess.create_file(path, text, options),
+            PlannedOperation::MovePath { ref from, ref to } => fsaccess.move_path(from, to),
+            PlannedOperation::RemoveDir { ref path } => fsaccess.remove_dir(path),
+            PlannedOperation::RemoveDirAll { ref path } => fsaccess.remove_dir_all(path),
+            PlannedOperation::RemoveFile { ref path } => fsaccess.remove_file(path),
+            PlannedOperation::RemoveXattr { ref path, ref name } => {
+                fsaccess.remove_xattr(path, name)
+            }
+            PlannedOperation::ReplaceFile {
+                ref path,
+                ref text,
+                ref options,
+            } => fsaccess.replace_file(path, text, options),
+            PlannedOperation::SetPermissions { ref path, mode } => {
+                fsaccess.set_permissions(path, mode)
+            }
+            PlannedOperation::SetTimes {
+                ref path,
+                atime,
+                mtime,
+            } => fsaccess.set_times(path, atime, mtime),
+            PlannedOperation::SetXattr {
+                ref path,
+                ref name,
+                ref value,
+            } => fsaccess.set_xattr(path, name, value),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Precondition {
+    pub exists: bool,
+    pub mtime: Option<SystemTime>,
+    pub parent_mtime: Option<SystemTime>,
+}
+
+impl Precondition {
+    pub fn capture(path: &Path) -> Precondition {
+        let metadata = path.symlink_metadata();
+        Precondition {
+            exists: metadata.is_ok(),
+            mtime: metadata.and_then(|md| md.modified()).ok(),
+            parent_mtime: path
+                .parent()
+                .and_then(|parent| parent.metadata().and_then(|md| md.modified()).ok()),
+        }
+    }
+
+    pub fn is_met(&self, path: &Path) -> bool {
+        *self == Precondition::capture(path)
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct PlanStep {
+    pub operation: PlannedOperation,
+    pub precondition: Precondition,
+}
+
+#[derive(Default, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Plan {
+    // Canonical root directories of the command planned, protected when applying the plan.
+    pub root_dirs: PathList,
+    pub steps: Vec<PlanStep>,
+}
+
+impl Plan {
+    pub fn read(path: &Path) -> Result<Plan> {
+        let file = std::fs::File::open(path)?;
+        serde_json::from_reader(file).map_err(|error| Error::new(ErrorKind::InvalidData, error))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(file, self).map_err(Error::other)
+    }
+}
+
+// Records operations instead of executing them.
+#[derive(Debug)]
+pub struct PlanningFileSystemAccess {
+    plan: Arc<Mutex<Plan>>,
+}
+
+impl PlanningFileSystemAccess {
+    pub fn new(plan: Arc<Mutex<Plan>>) -> PlanningFileSystemAccess {
+        PlanningFileSystemAccess { plan }
+    }
+
+    fn record(&self, operation: PlannedOperation) -> Result<()> {
+        let precondition = Precondition::capture(operation.get_path());
+        self.plan.lock().unwrap().steps.push(PlanStep {
+            operation,
+            precondition,
+        });
+        Ok(())
+    }
+}
+
+impl FileSystemAccess for PlanningFileSystemAccess {
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        self.record(PlannedOperation::CreateDirAll {
+            path: path.to_owned(),
+        })
+    }
+
+    fn create_file(&self, path: &Path, text: &str, options: &FileOptions) -> Result<()> {
+        self.record(PlannedOperation::CreateFile {
+            path: path.to_owned(),
+            text: text.to_owned(),
+            options: options.clone(),
+        })
+    }
+
+    fn replace_file(&self, path: &Path, text: &str, options: &FileOptions) -> Result<()> {
+        self.record(PlannedOperation::ReplaceFile {
+            path: path.to_owned(),
+            text: text.to_owned(),
+            options: options.clone(),
+        })
+    }
+
+    fn move_path(&self, from: &Path, to: &Path) -> Result<()> {
+        self.record(PlannedOperation::MovePath {
+            from: from.to_owned(),
+            to: to.to_owned(),
+        })
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        self.record(PlannedOperation::RemoveDir {
+            path: path.to_owned(),
+        })
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        self.record(PlannedOperation::RemoveDirAll {
+            path: path.to_owned(),
+        })
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        self.record(PlannedOperation::RemoveFile {
+            path: path.to_owned(),
+        })
+    }
+
+    fn remove_xattr(&self, path: &Path, name: &str) -> Result<()> {
+        self.record(PlannedOperation::RemoveXattr {
+            path: path.to_owned(),
+            name: name.to_owned(),
+        })
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+        self.record(PlannedOperation::SetPermissions {
+            path: path.to_owned(),
+            mode,
+        })
+    }
+
+    fn set_times(&self, path: &Path, atime: SystemTime, mtime: SystemTime) -> Result<()> {
+        self.record(PlannedOperation::SetTimes {
+            path: path.to_owned(),
+            atime,
+            mtime,
+        })
+    }
+
+    fn set_xattr(&self, path: &Path, name: &str, value: &str) -> Result<()> {
+        self.record(PlannedOperation::SetXattr {
+            path: path.to_owned(),
+            name: name.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+}
@@ -0,0 +1,260 @@
+use super::*;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+// Errors network file systems report occasionally, which may vanish when trying again.
+#[derive(Copy, Clone, PartialEq, Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TransientError {
+    Busy,
+    Interrupted,
+    Stale,
+    TimedOut,
+}
+
+impl TransientError {
+    pub fn all() -> Vec<TransientError> {
+        vec![
+            TransientError::Busy,
+            TransientError::Interrupted,
+            TransientError::Stale,
+            TransientError::TimedOut,
+        ]
+    }
+
+    pub fn of(error: &Error) -> Option<TransientError> {
+        match error.kind() {
+            ErrorKind::ResourceBusy => Some(TransientError::Busy),
+            ErrorKind::Interrupted => Some(TransientError::Interrupted),
+            ErrorKind::StaleNetworkFileHandle => Some(TransientError::Stale),
+            ErrorKind::TimedOut => Some(TransientError::TimedOut),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct RetryPolicy {
+    // Delay before the first retry, doubled for each further one.
+    pub initial_delay: Duration,
+    pub max_retries: u32,
+    pub retried_errors: Vec<TransientError>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy::new()
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> RetryPolicy {
+        RetryPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_retries: 3,
+            retried_errors: TransientError::all(),
+        }
+    }
+
+    // Runs the operation until it succeeds, fails for good or runs out of retries.
+    pub fn run<T, F>(&self, log: &RetryLog, operation: &str, path: &Path, mut op: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        let mut delay = self.initial_delay;
+        let mut retries = 0;
+        let mut last_error = String::new();
+        loop {
+            let result = op();
+            match result {
+                Err(ref error) if retries < self.max_retries && self.is_retried(error) => {
+                    last_error = error.to_string();
+                }
+                result => {
+                    if retries > 0 {
+                        log.record(RetryRecord {
+                            operation: operation.to_owned(),
+                            path: path.to_owned(),
+                            retries,
+                            error: match result {
+                                Err(ref error) => error.to_string(),
+                                Ok(_) => last_error,
+                            },
+                            succeeded: result.is_ok(),
+                        });
+                    }
+                    return result;
+                }
+            }
+            std::thread::sleep(delay);
+            delay *= 2;
+            retries += 1;
+        }
+    }
+
+    fn is_retried(&self, error: &Error) -> bool {
+        TransientError::of(error).is_some_and(|error| self.retried_errors.contains(&error))
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct RetryRecord {
+    pub operation: String,
+    pub path: PathBuf,
+    pub retries: u32,
+    // The error of the final attempt if failed, or else the one causing the last retry.
+    pub error: String,
+    pub succeeded: bool,
+}
+
+// Collects retries of parallel operations.
+#[derive(Default, Debug)]
+pub struct RetryLog {
+    records: Mutex<Vec<RetryRecord>>,
+}
+
+impl RetryLog {
+    pub fn record(&self, record: RetryRecord) {
+        self.records.lock().unwrap().push(record);
+    }
+
+    pub fn take(&self) -> Vec<RetryRecord> {
+        std::mem::take(&mut *self.records.lock().unwrap())
+    }
+}
+
+// Applies the retry policy to all operations of the wrapped file system access.
+#[derive(Debug)]
+pub struct RetryingFileSystemAccess {
+    inner: Box<dyn FileSystemAccess>,
+    log: Arc<RetryLog>,
+    policy: RetryPolicy,
+}
+
+impl RetryingFileSystemAccess {
+    pub fn new(
+        inner: Box<dyn FileSystemAccess>,
+        policy: RetryPolicy,
+        log: Arc<RetryLog>,
+    ) -> RetryingFileSystemAccess {
+        RetryingFileSystemAccess { inner, log, policy }
+    }
+
+    fn run<F>(&self, operation: &str, path: &Path, op: F) -> Result<()>
+    where
+        F: FnMut() -> Result<()>,
+    {
+        self.policy.run(&self.log, operation, path, op)
+    }
+
+    // Runs an operation failing if repeated after having succeeded, like creating a file. An
+    // attempt may succeed despite reporting a transient error, so a retry failing counts as
+    // success if the end state of the operation is confirmed.
+    fn run_once<F, D>(&self, operation: &str, path: &Path, mut op: F, is_done: D) -> Result<()>
+    where
+        F: FnMut() -> Result<()>,
+        D: Fn() -> bool,
+    {
+        let mut retrying = false;
+        self.run(operation, path, || {
+            let result = op();
+            let retried = std::mem::replace(&mut retrying, true);
+            match result {
+                Err(_) if retried && is_done() => Ok(()),
+                result => result,
+            }
+        })
+    }
+}
+
+fn is_missing(path: &Path) -> bool {
+    match path.symlink_metadata() {
+        Ok(_) => false,
+        Err(error) => error.kind() == ErrorKind::NotFound,
+    }
+}
+
+impl FileSystemAccess for RetryingFileSystemAccess {
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        self.run("create_dir_all", path, || self.inner.create_dir_all(path))
+    }
+
+    fn create_file(&self, path: &Path, text: &str, options: &FileOptions) -> Result<()> {
+        self.run_once(
+            "create_file",
+            path,
+            || self.inner.create_file(path, text, options),
+            || std::fs::read_to_string(path).is_ok_and(|content| content == text),
+        )
+    }
+
+    fn replace_file(&self, path: &Path, text: &str, options: &FileOptions) -> Result<()> {
+        self.run("replace_file", path, || {
+            self.inner.replace_file(path, text, options)
+        })
+    }
+
+    fn move_path(&self, from: &Path, to: &Path) -> Result<()> {
+        self.run_once(
+            "move_path",
+            from,
+            || self.inner.move_path(from, to),
+            || is_missing(from) && !is_missing(to),
+        )
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        self.run_once(
+            "remove_dir",
+            path,
+            || self.inner.remove_dir(path),
+            || is_missing(path),
+        )
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        self.run_once(
+            "remove_file",
+            path,
+            || self.inner.remove_file(path),
+            || is_missing(path),
+        )
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        self.run_once(
+            "remove_dir_all",
+            path,
+            || self.inner.remove_dir_all(path),
+            || is_missing(path),
+        )
+    }
+
+    fn remove_xattr(&self, path: &Path, name: &str) -> Result<()> {
+        self.run_once(
+            "remove_xattr",
+            path,
+            || self.inner.remove_xattr(path, name),
+            || get_xattr(path, name).is_ok_and(|value| value.is_none()),
+        )
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+        self.run("set_permissions", path, || {
+            self.inner.set_permissions(path, mode)
+        })
+    }
+
+    fn set_times(&self, path: &Path, atime: SystemTime, mtime: SystemTime) -> Result<()> {
+        self.run("set_times", path, || {
+            self.inner.set_times(path, atime, mtime)
+        })
+    }
+
+    fn set_xattr(&self, path: &Path, name: &str, value: &str) -> Result<()> {
+        self.run("set_xattr", path, || {
+            self.inner.set_xattr(path, name, value)
+        })
+    }
+}
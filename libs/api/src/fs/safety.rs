@@ -0,0 +1,111 @@
+use super::*;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Component;
+
+const SYSTEM_DIRS: &[&str] = &[
+    "/Applications",
+    "/Library",
+    "/System",
+    "/bin",
+    "/boot",
+    "/dev",
+    "/etc",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/opt",
+    "/private",
+    "/proc",
+    "/run",
+    "/sbin",
+    "/srv",
+    "/sys",
+    "/usr",
+    "/usr/bin",
+    "/usr/lib",
+    "/usr/local",
+    "/usr/sbin",
+    "/var",
+];
+
+// Returns true for file system roots, the home directory and its ancestors as well as
+// system directories, which are too precious to be operated on by accident.
+pub fn is_dangerous_root_dir(dir: &Path) -> bool {
+    let dir = match dir.canonicalize() {
+        Ok(dir) => dir,
+        Err(_) => return false,
+    };
+    if dir.parent().is_none() {
+        return true;
+    }
+
+    let home_dir = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .and_then(|home_dir| Path::new(&home_dir).canonicalize().ok());
+    if let Some(home_dir) = home_dir {
+        if home_dir.starts_with(&dir) {
+            return true;
+        }
+    }
+
+    SYSTEM_DIRS
+        .iter()
+        .any(|system_dir| dir == Path::new(system_dir))
+}
+
+// Accepts plain file names only, so that markers and manifests stay in their directory.
+pub fn validate_file_name(name: &str) -> Result<()> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) if !name.contains(&['/', '\\', '\0'][..]) => Ok(()),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("invalid file name '{}'", name),
+        )),
+    }
+}
+
+// Fails unless the path lies inside one of the canonical root directories. Symlinks of
+// the path's ancestors are resolved, but not the one of the path itself, which is the
+// one to be operated on.
+pub fn check_inside_root_dirs(root_dirs: &[PathBuf], path: &Path) -> Result<()> {
+    let outside_error = || {
+        Error::new(
+            ErrorKind::PermissionDenied,
+            "path outside of root directories",
+        )
+    };
+    if path
+        .components()
+        .any(|component| component == Component::ParentDir)
+    {
+        return Err(outside_error());
+    }
+
+    let path = get_absolute_dir(&path.to_owned())?;
+    let resolved_path = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => resolve_existing_ancestor(parent)?.join(name),
+        _ => path.canonicalize()?,
+    };
+    if root_dirs
+        .iter()
+        .any(|root_dir| resolved_path.starts_with(root_dir))
+    {
+        Ok(())
+    } else {
+        Err(outside_error())
+    }
+}
+
+// Canonicalizes the nearest existing ancestor, keeping the components not created yet.
+fn resolve_existing_ancestor(path: &Path) -> Result<PathBuf> {
+    let existing_dir = path
+        .ancestors()
+        .find(|dir| dir.exists())
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no existing ancestor"))?;
+    let mut resolved_path = existing_dir.canonicalize()?;
+    if let Ok(missing_part) = path.strip_prefix(existing_dir) {
+        resolved_path.push(missing_part);
+    }
+    Ok(resolved_path)
+}
@@ -0,0 +1,195 @@
+use super::*;
+use std::io::{Error, Result};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+// Limits applied separately to directory reads and to modifications, so that runs on shared
+// storage leave capacity for other users.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ThrottlePolicy {
+    pub max_concurrent_ops: Option<usize>,
+    pub max_ops_per_sec: Option<u32>,
+}
+
+impl Default for ThrottlePolicy {
+    fn default() -> ThrottlePolicy {
+        ThrottlePolicy::new()
+    }
+}
+
+impl ThrottlePolicy {
+    pub fn new() -> ThrottlePolicy {
+        ThrottlePolicy {
+            max_concurrent_ops: None,
+            max_ops_per_sec: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Throttle {
+    policy: ThrottlePolicy,
+    running_ops: Mutex<usize>,
+    op_finished: Condvar,
+    next_start: Mutex<Option<Instant>>,
+}
+
+impl Throttle {
+    pub fn new(policy: ThrottlePolicy) -> Throttle {
+        Throttle {
+            policy,
+            running_ops: Mutex::new(0),
+            op_finished: Condvar::new(),
+            next_start: Mutex::new(None),
+        }
+    }
+
+    // Runs the operation once both a concurrency slot and its turn within the rate are due.
+    pub fn run<T, F>(&self, op: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let _slot = self.acquire_slot();
+        self.wait_for_turn();
+        op()
+    }
+
+    fn acquire_slot(&self) -> Option<Slot<'_>> {
+        let max_concurrent_ops = self.policy.max_concurrent_ops?;
+        let mut running_ops = self.running_ops.lock().unwrap();
+        while *running_ops >= max_concurrent_ops {
+            running_ops = self.op_finished.wait(running_ops).unwrap();
+        }
+        *running_ops += 1;
+        Some(Slot { throttle: self })
+    }
+
+    // Spaces operations evenly instead of letting them burst at the start of each second.
+    fn wait_for_turn(&self) {
+        let max_ops_per_sec = match self.policy.max_ops_per_sec {
+            Some(max_ops_per_sec) => max_ops_per_sec,
+            None => return,
+        };
+        let start = {
+            let mut next_start = self.next_start.lock().unwrap();
+            let now = Instant::now();
+            let start = next_start.map_or(now, |next_start| next_start.max(now));
+            *next_start = Some(start + Duration::from_secs(1) / max_ops_per_sec);
+            start
+        };
+        let now = Instant::now();
+        if start > now {
+            std::thread::sleep(start - now);
+        }
+    }
+}
+
+struct Slot<'a> {
+    throttle: &'a Throttle,
+}
+
+impl<'a> Drop for Slot<'a> {
+    fn drop(&mut self) {
+        *self.throttle.running_ops.lock().unwrap() -= 1;
+        self.throttle.op_finished.notify_one();
+    }
+}
+
+// Applies the throttle to all operations of the wrapped file system access.
+#[derive(Debug)]
+pub struct ThrottlingFileSystemAccess {
+    inner: Box<dyn FileSystemAccess>,
+    throttle: Throttle,
+}
+
+impl ThrottlingFileSystemAccess {
+    pub fn new(
+        inner: Box<dyn FileSystemAccess>,
+        policy: ThrottlePolicy,
+    ) -> ThrottlingFileSystemAccess {
+        ThrottlingFileSystemAccess {
+            inner,
+            throttle: Throttle::new(policy),
+        }
+    }
+}
+
+impl FileSystemAccess for ThrottlingFileSystemAccess {
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        self.throttle.run(|| self.inner.create_dir_all(path))
+    }
+
+    fn create_file(&self, path: &Path, text: &str, options: &FileOptions) -> Result<()> {
+        self.throttle
+            .run(|| self.inner.create_file(path, text, options))
+    }
+
+    fn replace_file(&self, path: &Path, text: &str, options: &FileOptions) -> Result<()> {
+        self.throttle
+            .run(|| self.inner.replace_file(path, text, options))
+    }
+
+    fn move_path(&self, from: &Path, to: &Path) -> Result<()> {
+        self.throttle.run(|| self.inner.move_path(from, to))
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        self.throttle.run(|| self.inner.remove_dir(path))
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        self.throttle.run(|| self.inner.remove_file(path))
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        self.throttle.run(|| self.inner.remove_dir_all(path))
+    }
+
+    fn remove_xattr(&self, path: &Path, name: &str) -> Result<()> {
+        self.throttle.run(|| self.inner.remove_xattr(path, name))
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+        self.throttle.run(|| self.inner.set_permissions(path, mode))
+    }
+
+    fn set_times(&self, path: &Path, atime: SystemTime, mtime: SystemTime) -> Result<()> {
+        self.throttle
+            .run(|| self.inner.set_times(path, atime, mtime))
+    }
+
+    fn set_xattr(&self, path: &Path, name: &str, value: &str) -> Result<()> {
+        self.throttle
+            .run(|| self.inner.set_xattr(path, name, value))
+    }
+}
+
+// Moves the calling thread into the idle I/O scheduling class, like `ionice -c 3`. Threads
+// started afterwards inherit the priority.
+#[cfg(target_os = "linux")]
+pub fn set_low_io_priority() -> Result<()> {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0,
+            IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_low_io_priority() -> Result<()> {
+    Err(Error::new(
+        std::io::ErrorKind::Unsupported,
+        "I/O priorities are not supported on this platform",
+    ))
+}
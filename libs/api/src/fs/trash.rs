@@ -0,0 +1,250 @@
+use super::*;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use std::collections::HashSet;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::Mutex;
+
+const DELETION_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum TrashLocation {
+    Directory(PathBuf),
+    Xdg,
+}
+
+#[derive(Debug)]
+pub struct TrashEntry {
+    pub deletion_date: DateTime<Local>,
+    pub info_file: PathBuf,
+    pub original_path: PathBuf,
+    pub trashed_path: PathBuf,
+}
+
+// Trashed files are laid out as described by the FreeDesktop.org Trash specification,
+// i.e. in "files" and "info" sub-directories of a trash directory. A custom location
+// holds one such trash directory per run, named after the time the run started.
+#[derive(Debug)]
+pub struct Trash {
+    pub location: TrashLocation,
+    run_name: String,
+    // Names handed out by this trash, as moves may only be planned rather than executed.
+    reserved_names: Mutex<HashSet<String>>,
+}
+
+impl Trash {
+    pub fn new(location: TrashLocation) -> Trash {
+        Trash {
+            location,
+            run_name: format!(
+                "{}-{}",
+                Local::now().format("%Y%m%dT%H%M%S"),
+                std::process::id()
+            ),
+            reserved_names: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn get_entries(&self) -> Result<Vec<TrashEntry>> {
+        let mut entries = vec![];
+        for trash_dir in self.get_trash_dirs()? {
+            let info_dir = trash_dir.join("info");
+            if !info_dir.is_dir() {
+                continue;
+            }
+            for info_file in info_dir.read_dir()? {
+                let info_file = info_file?.path();
+                match read_entry(&trash_dir, &info_file) {
+                    Ok(entry) => entries.push(entry),
+                    Err(error) => warn!(target: "Trash", "{}: {}", to_native(&info_file), error),
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    pub fn put(&self, fsaccess: &dyn FileSystemAccess, path: &Path) -> Result<()> {
+        let mut reserved_names = self.reserved_names.lock().unwrap();
+
+        let trash_dir = match self.location {
+            TrashLocation::Directory(ref dir) => get_absolute_dir(dir)?.join(&self.run_name),
+            TrashLocation::Xdg => get_xdg_trash_dir()?,
+        };
+        let files_dir = trash_dir.join("files");
+        let info_dir = trash_dir.join("info");
+        fsaccess.create_dir_all(&files_dir)?;
+        fsaccess.create_dir_all(&info_dir)?;
+
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "path without file name"))?
+            .to_string_lossy()
+            .into_owned();
+        let mut name = file_name.clone();
+        let mut counter = 1;
+        while reserved_names.contains(&name)
+            || files_dir.join(&name).symlink_metadata().is_ok()
+            || info_dir.join(format!("{}.trashinfo", name)).exists()
+        {
+            counter += 1;
+            name = format!("{}.{}", file_name, counter);
+        }
+        reserved_names.insert(name.clone());
+
+        let info_file = info_dir.join(format!("{}.trashinfo", name));
+        let info = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode_path(&get_absolute_dir(&path.to_owned())?),
+            Local::now().format(DELETION_DATE_FORMAT)
+        );
+        fsaccess.create_file(&info_file, &info, &FileOptions::default())?;
+
+        if let Err(error) = fsaccess.move_path(path, &files_dir.join(&name)) {
+            let _ = fsaccess.remove_file(&info_file);
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, fsaccess: &dyn FileSystemAccess, entry: &TrashEntry) -> Result<()> {
+        match entry.trashed_path.symlink_metadata() {
+            Ok(ref md) if md.is_dir() => fsaccess.remove_dir_all(&entry.trashed_path)?,
+            Ok(_) => fsaccess.remove_file(&entry.trashed_path)?,
+            Err(_) => (),
+        }
+        fsaccess.remove_file(&entry.info_file)
+    }
+
+    pub fn remove_empty_trash_dirs(&self, fsaccess: &dyn FileSystemAccess) -> Result<()> {
+        if let TrashLocation::Xdg = self.location {
+            return Ok(());
+        }
+        for trash_dir in self.get_trash_dirs()? {
+            let files_dir = trash_dir.join("files");
+            let info_dir = trash_dir.join("info");
+            if is_empty_dir(&files_dir) && is_empty_dir(&info_dir) {
+                fsaccess.remove_dir(&files_dir)?;
+                fsaccess.remove_dir(&info_dir)?;
+                fsaccess.remove_dir(&trash_dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn restore(&self, fsaccess: &dyn FileSystemAccess, entry: &TrashEntry) -> Result<()> {
+        if entry.original_path.symlink_metadata().is_ok() {
+            return Err(Error::new(ErrorKind::AlreadyExists, "original path exists"));
+        }
+        if let Some(parent) = entry.original_path.parent() {
+            fsaccess.create_dir_all(parent)?;
+        }
+        fsaccess.move_path(&entry.trashed_path, &entry.original_path)?;
+        fsaccess.remove_file(&entry.info_file)
+    }
+
+    fn get_trash_dirs(&self) -> Result<PathList> {
+        match self.location {
+            TrashLocation::Directory(ref dir) => {
+                let dir = get_absolute_dir(dir)?;
+                if !dir.is_dir() {
+                    return Ok(vec![]);
+                }
+                let mut trash_dirs = vec![];
+                for entry in dir.read_dir()? {
+                    let path = entry?.path();
+                    if path.join("info").is_dir() {
+                        trash_dirs.push(path);
+                    }
+                }
+                Ok(trash_dirs)
+            }
+            TrashLocation::Xdg => Ok(vec![get_xdg_trash_dir()?]),
+        }
+    }
+}
+
+fn get_xdg_trash_dir() -> Result<PathBuf> {
+    let data_dir = match std::env::var_os("XDG_DATA_HOME") {
+        Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match std::env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".local").join("share"),
+            None => return Err(Error::new(ErrorKind::NotFound, "HOME not set")),
+        },
+    };
+    Ok(data_dir.join("Trash"))
+}
+
+fn is_empty_dir(dir: &Path) -> bool {
+    dir.read_dir()
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(false)
+}
+
+fn read_entry(trash_dir: &Path, info_file: &Path) -> Result<TrashEntry> {
+    let name = info_file
+        .file_stem()
+        .filter(|_| info_file.extension().is_some_and(|ext| ext == "trashinfo"))
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "no trash info file"))?;
+
+    let info = std::fs::read_to_string(info_file)?;
+    let mut original_path = None;
+    let mut deletion_date = None;
+    for line in info.lines() {
+        if let Some(path) = line.strip_prefix("Path=") {
+            original_path = Some(decode_path(path));
+        } else if let Some(date) = line.strip_prefix("DeletionDate=") {
+            deletion_date = NaiveDateTime::parse_from_str(date, DELETION_DATE_FORMAT)
+                .ok()
+                .and_then(|date| Local.from_local_datetime(&date).earliest());
+        }
+    }
+
+    match (original_path, deletion_date) {
+        (Some(original_path), Some(deletion_date)) => Ok(TrashEntry {
+            deletion_date,
+            info_file: info_file.to_owned(),
+            original_path,
+            trashed_path: trash_dir.join("files").join(name),
+        }),
+        _ => Err(Error::new(ErrorKind::InvalidData, "incomplete trash info")),
+    }
+}
+
+fn encode_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn decode_path(text: &str) -> PathBuf {
+    let bytes = text.as_bytes();
+    let mut decoded = vec![];
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = if bytes[index] == b'%' && index + 2 < bytes.len() {
+            std::str::from_utf8(&bytes[index + 1..index + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&decoded).into_owned())
+}
@@ -0,0 +1,155 @@
+use super::*;
+use std::io::{Error, ErrorKind, Result};
+
+pub const MARKER_XATTR_NAME: &str = "user.markemptydirs";
+
+// Returns None if the attribute is not set or extended attributes are not supported.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn get_xattr(path: &Path, name: &str) -> Result<Option<Vec<u8>>> {
+    let (path, name) = (to_c_string(path.as_os_str())?, to_c_string(name.as_ref())?);
+    loop {
+        let size = unsafe { sys::getxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+        if size < 0 {
+            return handle_missing_xattr(Error::last_os_error());
+        }
+        let mut value = vec![0u8; size as usize];
+        let size = unsafe {
+            sys::getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr() as *mut libc::c_void,
+                value.len(),
+            )
+        };
+        if size < 0 {
+            let error = Error::last_os_error();
+            // The attribute has grown in the meantime.
+            if error.raw_os_error() == Some(libc::ERANGE) {
+                continue;
+            }
+            return handle_missing_xattr(error);
+        }
+        value.truncate(size as usize);
+        return Ok(Some(value));
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn get_xattr(_path: &Path, _name: &str) -> Result<Option<Vec<u8>>> {
+    Ok(None)
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn set_xattr(path: &Path, name: &str, value: &[u8]) -> Result<()> {
+    let (path, name) = (to_c_string(path.as_os_str())?, to_c_string(name.as_ref())?);
+    let result = unsafe {
+        sys::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr() as *const libc::c_void,
+            value.len(),
+        )
+    };
+    if result < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn set_xattr(_path: &Path, _name: &str, _value: &[u8]) -> Result<()> {
+    Err(Error::new(
+        ErrorKind::Other,
+        "extended attributes not supported",
+    ))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn remove_xattr(path: &Path, name: &str) -> Result<()> {
+    let (path, name) = (to_c_string(path.as_os_str())?, to_c_string(name.as_ref())?);
+    if unsafe { sys::removexattr(path.as_ptr(), name.as_ptr()) } < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn remove_xattr(_path: &Path, _name: &str) -> Result<()> {
+    Err(Error::new(
+        ErrorKind::Other,
+        "extended attributes not supported",
+    ))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn to_c_string(text: &std::ffi::OsStr) -> Result<std::ffi::CString> {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::CString::new(text.as_bytes())
+        .map_err(|error| Error::new(ErrorKind::InvalidInput, error))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn handle_missing_xattr(error: Error) -> Result<Option<Vec<u8>>> {
+    match error.raw_os_error() {
+        Some(sys::ENOATTR) | Some(libc::ENOTSUP) => Ok(None),
+        _ => Err(error),
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use libc::{c_char, c_int, c_void, size_t, ssize_t};
+
+    pub use libc::ENODATA as ENOATTR;
+
+    pub unsafe fn getxattr(
+        path: *const c_char,
+        name: *const c_char,
+        value: *mut c_void,
+        size: size_t,
+    ) -> ssize_t {
+        libc::getxattr(path, name, value, size)
+    }
+
+    pub unsafe fn setxattr(
+        path: *const c_char,
+        name: *const c_char,
+        value: *const c_void,
+        size: size_t,
+    ) -> c_int {
+        libc::setxattr(path, name, value, size, 0)
+    }
+
+    pub unsafe fn removexattr(path: *const c_char, name: *const c_char) -> c_int {
+        libc::removexattr(path, name)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use libc::{c_char, c_int, c_void, size_t, ssize_t};
+
+    pub use libc::ENOATTR;
+
+    pub unsafe fn getxattr(
+        path: *const c_char,
+        name: *const c_char,
+        value: *mut c_void,
+        size: size_t,
+    ) -> ssize_t {
+        libc::getxattr(path, name, value, size, 0, 0)
+    }
+
+    pub unsafe fn setxattr(
+        path: *const c_char,
+        name: *const c_char,
+        value: *const c_void,
+        size: size_t,
+    ) -> c_int {
+        libc::setxattr(path, name, value, size, 0, 0)
+    }
+
+    pub unsafe fn removexattr(path: *const c_char, name: *const c_char) -> c_int {
+        libc::removexattr(path, name, 0)
+    }
+}
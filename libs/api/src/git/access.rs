@@ -0,0 +1,59 @@
+use super::{run_git, GitRepository};
+use std::io::Result;
+use std::path::PathBuf;
+
+pub trait GitAccess: Sync + std::fmt::Debug {
+    fn stage(&self, repo: &GitRepository, paths: &[PathBuf]) -> Result<()>;
+
+    fn unstage(&self, repo: &GitRepository, paths: &[PathBuf]) -> Result<()>;
+}
+
+pub fn create_git_access(dry_run: bool) -> Box<dyn GitAccess> {
+    if dry_run {
+        Box::new(DryRunGitAccess {})
+    } else {
+        Box::new(RealGitAccess {})
+    }
+}
+
+#[derive(Debug)]
+struct RealGitAccess {}
+
+impl RealGitAccess {
+    fn run_with_paths(&self, repo: &GitRepository, args: &[&str], paths: &[PathBuf]) -> Result<()> {
+        if paths.is_empty() {
+            return Ok(());
+        }
+        let mut args = args.to_vec();
+        args.push("--");
+        args.extend(paths.iter().filter_map(|path| path.to_str()));
+        run_git(&repo.work_tree, &args).map(|_| ())
+    }
+}
+
+impl GitAccess for RealGitAccess {
+    fn stage(&self, repo: &GitRepository, paths: &[PathBuf]) -> Result<()> {
+        self.run_with_paths(repo, &["add"], paths)
+    }
+
+    fn unstage(&self, repo: &GitRepository, paths: &[PathBuf]) -> Result<()> {
+        self.run_with_paths(
+            repo,
+            &["rm", "--cached", "--ignore-unmatch", "--quiet"],
+            paths,
+        )
+    }
+}
+
+#[derive(Debug)]
+struct DryRunGitAccess {}
+
+impl GitAccess for DryRunGitAccess {
+    fn stage(&self, _repo: &GitRepository, _paths: &[PathBuf]) -> Result<()> {
+        Ok(())
+    }
+
+    fn unstage(&self, _repo: &GitRepository, _paths: &[PathBuf]) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,5 @@
+mod access;
+pub use self::access::*;
+
+mod repository;
+pub use self::repository::*;
@@ -0,0 +1,93 @@
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct GitRepository {
+    pub work_tree: PathBuf,
+}
+
+impl GitRepository {
+    pub fn discover(dir: &Path) -> Result<GitRepository> {
+        let output = run_git(dir, &["rev-parse", "--show-toplevel"])?;
+        let work_tree = PathBuf::from(output.trim_end_matches('\n')).canonicalize()?;
+        Ok(GitRepository { work_tree })
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.work_tree)
+    }
+
+    pub fn hooks_dir(&self) -> Result<PathBuf> {
+        // Honors core.hooksPath as well as linked work trees.
+        let output = run_git(&self.work_tree, &["rev-parse", "--git-path", "hooks"])?;
+        Ok(self.work_tree.join(output.trim_end_matches('\n')))
+    }
+
+    pub fn ignored_dirs(&self) -> Result<Vec<PathBuf>> {
+        let output = run_git(
+            &self.work_tree,
+            &[
+                "ls-files",
+                "-z",
+                "--others",
+                "--ignored",
+                "--exclude-standard",
+                "--directory",
+            ],
+        )?;
+        Ok(output
+            .split('\0')
+            .filter(|path| path.ends_with('/'))
+            .map(|path| self.work_tree.join(path.trim_end_matches('/')))
+            .collect())
+    }
+
+    pub fn index_files(&self) -> Result<Vec<PathBuf>> {
+        let output = run_git(&self.work_tree, &["ls-files", "-z"])?;
+        Ok(self.to_absolute_paths(&output))
+    }
+
+    pub fn head_files(&self) -> Result<Vec<PathBuf>> {
+        let output = run_git(
+            &self.work_tree,
+            &["ls-tree", "-r", "-z", "--name-only", "--full-tree", "HEAD"],
+        )?;
+        Ok(self.to_absolute_paths(&output))
+    }
+
+    pub fn staged_paths(&self) -> Result<Vec<PathBuf>> {
+        let output = run_git(
+            &self.work_tree,
+            &["diff", "--cached", "--name-only", "-z", "--no-renames"],
+        )?;
+        Ok(self.to_absolute_paths(&output))
+    }
+
+    pub fn status(&self, paths: &[PathBuf]) -> Result<Vec<String>> {
+        if paths.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut args = vec!["status", "--porcelain", "--"];
+        args.extend(paths.iter().filter_map(|path| path.to_str()));
+        let output = run_git(&self.work_tree, &args)?;
+        Ok(output.lines().map(|line| line.to_owned()).collect())
+    }
+
+    fn to_absolute_paths(&self, output: &str) -> Vec<PathBuf> {
+        output
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .map(|path| self.work_tree.join(path))
+            .collect()
+    }
+}
+
+pub fn run_git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git").current_dir(dir).args(args).output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::other(stderr.trim().to_owned()));
+    }
+    String::from_utf8(output.stdout).map_err(|error| Error::new(ErrorKind::InvalidData, error))
+}
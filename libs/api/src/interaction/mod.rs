@@ -0,0 +1,12 @@
+mod stdin;
+pub use self::stdin::*;
+
+use std::fmt::Debug;
+
+pub trait Prompter: Debug + Sync {
+    // Returns the index of the chosen answer, or `default` if no answer could be read.
+    fn ask(&self, question: &str, answers: &[&str], default: usize) -> usize;
+
+    // Returns whether answers can be given, or else asking would silently pick defaults.
+    fn is_interactive(&self) -> bool;
+}
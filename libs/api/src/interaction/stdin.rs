@@ -0,0 +1,44 @@
+use super::Prompter;
+use std::io::{IsTerminal, Write};
+
+#[derive(Debug)]
+pub struct StdinPrompter {}
+
+impl StdinPrompter {
+    pub fn create() -> Box<dyn Prompter> {
+        Box::new(StdinPrompter {})
+    }
+}
+
+impl Prompter for StdinPrompter {
+    fn ask(&self, question: &str, answers: &[&str], default: usize) -> usize {
+        let choices: Vec<_> = answers
+            .iter()
+            .map(|answer| format!("[{}]{}", &answer[..1], &answer[1..]))
+            .collect();
+
+        loop {
+            // Keep stdout free for reports.
+            eprint!("{} {}? ", question, choices.join("/"));
+            let _ = std::io::stderr().flush();
+
+            let mut line = String::new();
+            match std::io::stdin().read_line(&mut line) {
+                Ok(0) | Err(_) => return default,
+                Ok(_) => (),
+            }
+
+            let line = line.trim().to_lowercase();
+            if let Some(index) = answers
+                .iter()
+                .position(|answer| !line.is_empty() && answer.starts_with(&line))
+            {
+                return index;
+            }
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        std::io::stdin().is_terminal()
+    }
+}
@@ -1,5 +1,9 @@
+extern crate chrono;
+
 // extern crate handlebars;
 
+extern crate libc;
+
 #[macro_use]
 extern crate log;
 
@@ -10,12 +14,23 @@ extern crate quick_error;
 
 extern crate rayon;
 
+extern crate serde;
+
+#[macro_use]
+extern crate serde_derive;
+
+extern crate serde_json;
+
 pub mod application;
 
 pub mod commands;
 
 pub mod fs;
 
+pub mod git;
+
+pub mod interaction;
+
 pub mod notification;
 
 quick_error! {
@@ -30,8 +45,9 @@ quick_error! {
         }
         Message(str: &'static str) {
             description("error message")
+            display("{}", str)
         }
-        Other(err: Box<std::error::Error>) {
+        Other(err: Box<dyn std::error::Error + Send + Sync>) {
             cause(&**err)
             description(err.description())
         }
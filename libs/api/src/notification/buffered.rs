@@ -0,0 +1,68 @@
+use super::Error;
+use super::LogLevel;
+use super::Notifier;
+use std::sync::Mutex;
+
+#[derive(Debug)]
+struct Message {
+    log_level: LogLevel,
+    target: String,
+    info: String,
+    data: String,
+    error: Option<Error>,
+}
+
+// Collects notifications of parallel operations and emits them in path order on flush, at
+// the expense of holding all of them until then.
+#[derive(Debug)]
+pub struct BufferedNotifier {
+    inner: Box<dyn Notifier>,
+    messages: Mutex<Vec<Message>>,
+}
+
+impl BufferedNotifier {
+    pub fn create(inner: Box<dyn Notifier>) -> Box<dyn Notifier> {
+        Box::new(BufferedNotifier {
+            inner,
+            messages: Mutex::default(),
+        })
+    }
+}
+
+impl Notifier for BufferedNotifier {
+    fn get_log_level(&self) -> LogLevel {
+        self.inner.get_log_level()
+    }
+
+    fn notify(
+        &self,
+        log_level: LogLevel,
+        target: &str,
+        info: &str,
+        data: &str,
+        error: Option<Error>,
+    ) {
+        if self.get_log_level() < log_level {
+            return;
+        }
+
+        self.messages.lock().unwrap().push(Message {
+            log_level,
+            target: target.to_owned(),
+            info: info.to_owned(),
+            data: data.to_owned(),
+            error,
+        });
+    }
+
+    fn flush(&self) {
+        let mut messages = std::mem::take(&mut *self.messages.lock().unwrap());
+        // Stable sort keeps the order of messages concerning the same path.
+        messages.sort_by(|a, b| a.data.cmp(&b.data));
+        for msg in messages {
+            self.inner
+                .notify(msg.log_level, &msg.target, &msg.info, &msg.data, msg.error);
+        }
+        self.inner.flush();
+    }
+}
@@ -1,3 +1,6 @@
+mod buffered;
+pub use self::buffered::*;
+
 mod logger;
 pub use self::logger::*;
 
@@ -27,6 +30,9 @@ pub trait Notifier: Debug + Sync {
         error: Option<Error>,
     );
 
+    // Emits messages held back so far.
+    fn flush(&self) {}
+
     fn debug(&self, target: &str, info: &str, data: &str, error: Option<Error>) {
         self.notify(LogLevel::Debug, target, info, data, error);
     }
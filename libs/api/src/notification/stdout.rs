@@ -22,21 +22,17 @@ impl std::fmt::Debug for StdoutNotifier {
     }
 }
 
-impl Notifier for StdoutNotifier {
-    fn get_log_level(&self) -> LogLevel {
-        self.log_level
-    }
-
-    fn notify(
+impl StdoutNotifier {
+    fn format_message(
         &self,
         log_level: LogLevel,
         target: &str,
         info: &str,
         data: &str,
         error: Option<Error>,
-    ) {
+    ) -> Option<String> {
         if self.log_level < log_level {
-            return;
+            return None;
         }
 
         let msg = if let Some(err) = error {
@@ -51,6 +47,61 @@ impl Notifier for StdoutNotifier {
             }
         };
 
-        println!("{}", msg);
+        Some(msg)
+    }
+}
+
+impl Notifier for StdoutNotifier {
+    fn get_log_level(&self) -> LogLevel {
+        self.log_level
+    }
+
+    fn notify(
+        &self,
+        log_level: LogLevel,
+        target: &str,
+        info: &str,
+        data: &str,
+        error: Option<Error>,
+    ) {
+        if let Some(msg) = self.format_message(log_level, target, info, data, error) {
+            println!("{}", msg);
+        }
+    }
+}
+
+// Keeps stdout free for machine-readable output.
+#[derive(Debug)]
+pub struct StderrNotifier {
+    formatter: StdoutNotifier,
+}
+
+impl StderrNotifier {
+    pub fn create(log_level: LogLevel, message_length: MessageLength) -> Box<dyn Notifier> {
+        Box::new(StderrNotifier {
+            formatter: StdoutNotifier {
+                log_level,
+                message_length,
+            },
+        })
+    }
+}
+
+impl Notifier for StderrNotifier {
+    fn get_log_level(&self) -> LogLevel {
+        self.formatter.log_level
+    }
+
+    fn notify(
+        &self,
+        log_level: LogLevel,
+        target: &str,
+        info: &str,
+        data: &str,
+        error: Option<Error>,
+    ) {
+        if let Some(msg) = self.formatter.format_message(log_level, target, info, data, error) {
+            eprintln!("{}", msg);
+        }
     }
 }
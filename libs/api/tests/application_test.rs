@@ -3,6 +3,9 @@
 extern crate api;
 
 use api::application::*;
+use api::commands::{Action, Apply, Config, Execution, Outcome, Update};
+use api::fs::{Plan, PlanStep, PlannedOperation, Precondition};
+use api::notification::LoggerNotifier;
 
 
 mod test_VersionInfo {
@@ -18,3 +21,200 @@ mod test_VersionInfo {
         assert_eq!("1976.10.3-stable", VersionInfo::new_with_suffix(1976, 10, 3, "stable").to_string());
     }
 }
+
+mod test_MarkEmptyDirs {
+    use super::*;
+
+    #[test]
+    fn update_and_clean() {
+        let root_dir = std::env::temp_dir().join("markemptydirs_builder_test");
+        let _ = std::fs::remove_dir_all(&root_dir);
+        std::fs::create_dir_all(root_dir.join("empty")).unwrap();
+        std::fs::create_dir_all(root_dir.join("skipped")).unwrap();
+
+        let report = MarkEmptyDirs::new(&root_dir)
+            .marker_name(".keep")
+            .exclude("skipped")
+            .update()
+            .unwrap();
+
+        assert!(report.is_success());
+        assert_eq!(1, report.actions.len());
+        assert_eq!(Action::CreateMarker, report.actions[0].action);
+        assert_eq!(Outcome::Done, report.actions[0].outcome);
+        assert!(root_dir.join("empty").join(".keep").exists());
+        assert!(!root_dir.join("skipped").join(".keep").exists());
+
+        let report = MarkEmptyDirs::new(&root_dir)
+            .marker_name(".keep")
+            .clean()
+            .unwrap();
+
+        assert_eq!(Action::DeleteMarker, report.actions[0].action);
+        assert!(!root_dir.join("empty").join(".keep").exists());
+    }
+    #[test]
+    fn prune() {
+        let root_dir = std::env::temp_dir().join("markemptydirs_prune_test");
+        let _ = std::fs::remove_dir_all(&root_dir);
+        std::fs::create_dir_all(root_dir.join("a").join("b").join("c")).unwrap();
+        std::fs::create_dir_all(root_dir.join("marked")).unwrap();
+        std::fs::write(root_dir.join("marked").join(".keep"), "").unwrap();
+
+        let report = MarkEmptyDirs::new(&root_dir)
+            .marker_name(".keep")
+            .prune()
+            .unwrap();
+
+        assert!(report.is_success());
+        assert_eq!(3, report.actions.len());
+        assert!(report.actions.iter().all(|record| record.action == Action::DeleteEmptyDir));
+        assert!(!root_dir.join("a").exists());
+        assert!(root_dir.join("marked").exists());
+        assert!(root_dir.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn purge_keeps_symlinked_dirs_outside_of_root() {
+        let temp_dir = std::env::temp_dir().join("markemptydirs_purge_symlink_test");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let (root_dir, outside_dir) = (temp_dir.join("root"), temp_dir.join("outside"));
+        std::fs::create_dir_all(&root_dir).unwrap();
+        std::fs::create_dir_all(&outside_dir).unwrap();
+        std::fs::write(outside_dir.join(".emptydir"), "").unwrap();
+        std::fs::write(outside_dir.join("precious"), "").unwrap();
+        std::os::unix::fs::symlink(&outside_dir, root_dir.join("link")).unwrap();
+
+        let report = MarkEmptyDirs::new(&root_dir)
+            .dereference_symlinks(true)
+            .purge()
+            .unwrap();
+
+        assert!(!report.is_success());
+        assert!(outside_dir.join("precious").exists());
+    }
+
+    #[test]
+    fn sign_markers() {
+        let root_dir = std::env::temp_dir().join("markemptydirs_sign_test");
+        let _ = std::fs::remove_dir_all(&root_dir);
+        std::fs::create_dir_all(root_dir.join("unsigned")).unwrap();
+
+        MarkEmptyDirs::new(root_dir.join("unsigned"))
+            .marker_text("text")
+            .configure(|cfg| cfg.sign_markers = false)
+            .update()
+            .unwrap();
+        std::fs::create_dir_all(root_dir.join("signed")).unwrap();
+        MarkEmptyDirs::new(root_dir.join("signed"))
+            .marker_text("text")
+            .update()
+            .unwrap();
+
+        let read_marker = |dir: &str| {
+            std::fs::read_to_string(root_dir.join(dir).join(".emptydir")).unwrap()
+        };
+        assert_eq!("text", read_marker("unsigned"));
+        assert!(read_marker("signed").starts_with("markemptydirs: signature=markemptydirs/"));
+        assert!(read_marker("signed").ends_with("\ntext"));
+    }
+}
+
+mod test_Apply {
+    use super::*;
+
+    fn create_root_dir(name: &str) -> std::path::PathBuf {
+        let root_dir = std::env::temp_dir().join(format!("markemptydirs_{}", name));
+        let _ = std::fs::remove_dir_all(&root_dir);
+        std::fs::create_dir_all(root_dir.join("a")).unwrap();
+        std::fs::create_dir_all(root_dir.join("b")).unwrap();
+        root_dir.canonicalize().unwrap()
+    }
+
+    fn run(exec: Execution) -> api::commands::Result<api::commands::Report> {
+        Session::new(
+            ApplicationInfo::new(),
+            Config::new(),
+            exec,
+            LoggerNotifier::create,
+        )
+        .run()
+    }
+
+    fn plan_update(root_dir: &std::path::Path, plan_file: &std::path::Path) -> Plan {
+        let mut cmd = Update::new();
+        cmd.root_dirs = vec![root_dir.to_owned()];
+        run(Execution::Plan(Box::new(cmd), plan_file.to_owned())).unwrap();
+        Plan::read(plan_file).unwrap()
+    }
+
+    fn apply(plan_file: &std::path::Path) -> api::commands::Result<api::commands::Report> {
+        let mut cmd = Apply::new();
+        cmd.plan_file = plan_file.to_owned();
+        run(Execution::Run(Box::new(cmd)))
+    }
+
+    #[test]
+    fn applies_planned_steps() {
+        let root_dir = create_root_dir("apply_test");
+        let plan_file = root_dir.with_extension("json");
+
+        let plan = plan_update(&root_dir, &plan_file);
+        assert_eq!(vec![root_dir.clone()], plan.root_dirs);
+        assert_eq!(2, plan.steps.len());
+        assert!(!root_dir.join("a").join(".emptydir").exists());
+
+        let report = apply(&plan_file).unwrap();
+        assert!(report.is_success());
+        assert!(root_dir.join("a").join(".emptydir").exists());
+        assert!(root_dir.join("b").join(".emptydir").exists());
+    }
+
+    #[test]
+    fn aborts_on_drift_before_changes() {
+        let root_dir = create_root_dir("apply_failure_test");
+        let plan_file = root_dir.with_extension("json");
+
+        let plan = plan_update(&root_dir, &plan_file);
+        let first_path = plan.steps[0].operation.get_path().clone();
+        let second_path = plan.steps[1].operation.get_path().clone();
+        std::fs::write(&second_path, "changed").unwrap();
+
+        assert!(apply(&plan_file).is_err());
+        assert!(!first_path.exists());
+        assert_eq!("changed", std::fs::read_to_string(&second_path).unwrap());
+    }
+
+    #[test]
+    fn refuses_steps_outside_of_root_dirs() {
+        let root_dir = create_root_dir("apply_outside_test");
+        let plan_file = root_dir.with_extension("json");
+        let outside_file = root_dir.with_extension("txt");
+        std::fs::write(&outside_file, "text").unwrap();
+
+        let mut plan = plan_update(&root_dir, &plan_file);
+        let first_path = plan.steps[0].operation.get_path().clone();
+        plan.steps.push(PlanStep {
+            operation: PlannedOperation::RemoveFile {
+                path: outside_file.clone(),
+            },
+            precondition: Precondition::capture(&outside_file),
+        });
+        plan.write(&plan_file).unwrap();
+
+        assert!(apply(&plan_file).is_err());
+        assert!(!first_path.exists());
+        assert!(outside_file.exists());
+    }
+
+    #[test]
+    fn refuses_dangerous_root_dirs() {
+        let plan_file = std::env::temp_dir().join("markemptydirs_apply_dangerous_test.json");
+        let mut plan = Plan::default();
+        plan.root_dirs = vec![std::path::PathBuf::from("/")];
+        plan.write(&plan_file).unwrap();
+
+        assert!(apply(&plan_file).is_err());
+    }
+}
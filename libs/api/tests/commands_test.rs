@@ -7,7 +7,14 @@ use common::*;
 
 use api::commands::*;
 use api::fs::*;
+use api::git::{run_git, GitRepository};
 use api::notification::{LogLevel, MessageLength};
+use std::time::Duration;
+
+fn init_git_repo(tree: &TempTree) -> GitRepository {
+    run_git(&tree.root_dir, &["init", "-q"]).unwrap();
+    GitRepository::discover(&tree.root_dir).unwrap()
+}
 
 mod test_Config {
     use super::*;
@@ -20,12 +27,34 @@ mod test_Config {
     #[test]
     pub fn new() {
         let expected = Config {
+            applied_env_vars: vec![],
+            emptiness: Emptiness::FileSystem,
             exclude_dirs: vec![Path::new(".git").to_owned()],
             executable_file: PathBuf::new(),
+            force: false,
+            inherit_marker_owner: false,
+            jobs: None,
+            json_report: false,
+            lock_root_dirs: true,
+            lock_timeout: Duration::from_secs(0),
             log_level: LogLevel::Error,
             message_length: MessageLength::Long,
+            manifest_name: ".emptydirs".to_string(),
+            marker_mode: None,
+            marker_mtime: None,
             marker_name: ".emptydir".to_string(),
+            marker_strategy: MarkerStrategy::File,
             dereference_symlinks: false,
+            low_io_priority: false,
+            preserve_dir_times: false,
+            reproducible: false,
+            retry_policy: RetryPolicy::new(),
+            selected_paths: None,
+            select_parents: false,
+            sign_markers: true,
+            source_date_epoch: None,
+            throttle_policy: ThrottlePolicy::new(),
+            trash: None,
         };
 
         assert_eq!(expected, Config::new());
@@ -38,9 +67,12 @@ mod test_Clean {
     #[test]
     pub fn new() {
         let expected = Clean {
+            confirmation: Confirmation::Never,
             delete_hook: String::new(),
             dry_run: false,
+            git: false,
             root_dirs: Config::default_root_dirs(),
+            strict: false,
         };
 
         assert_eq!(expected, Clean::new());
@@ -50,10 +82,442 @@ mod test_Clean {
     pub fn execute() {
         let ctx = TestContext::new(|_| DirDescriptorList::new());
         let sut = Clean::new();
-        
+
         sut.execute(&ctx).unwrap();
 
         assert_eq!(0, ctx.get_markers_created().len());
         assert_eq!(0, ctx.get_markers_deleted().len());
     }
+
+    #[test]
+    pub fn execute_deletes_markers() {
+        let tree = TempTree::new("clean");
+        tree.create(&["a/.emptydir", "a/file", "b/.emptydir"]);
+        let mut sut = Clean::new();
+        sut.root_dirs = vec![tree.root_dir.clone()];
+
+        run(Config::new(), Box::new(sut)).unwrap();
+
+        assert!(!tree.exists("a/.emptydir"));
+        assert!(tree.exists("a/file"));
+        assert!(!tree.exists("b/.emptydir"));
+        assert!(tree.exists("b"));
+    }
+
+    #[test]
+    pub fn execute_obeys_directives() {
+        let tree = TempTree::new("clean_directives");
+        tree.write("a/.emptydir", "markemptydirs: delete=never\n");
+        tree.write("b/.emptydir", "markemptydirs: purge=on-clean\n");
+        tree.create(&["a/file", "b/file", "b/c/file"]);
+        let mut sut = Clean::new();
+        sut.root_dirs = vec![tree.root_dir.clone()];
+
+        run(Config::new(), Box::new(sut)).unwrap();
+
+        assert!(tree.exists("a/.emptydir"));
+        assert!(tree.exists("a/file"));
+        assert!(!tree.exists("b/.emptydir"));
+        assert!(!tree.exists("b/file"));
+        assert!(!tree.exists("b/c"));
+        assert!(tree.exists("b"));
+    }
+
+    #[test]
+    pub fn execute_with_git() {
+        let tree = TempTree::new("clean_git");
+        let repo = init_git_repo(&tree);
+        tree.create(&["a/.emptydir"]);
+        run_git(&tree.root_dir, &["add", "a/.emptydir"]).unwrap();
+        let mut sut = Clean::new();
+        sut.git = true;
+        sut.root_dirs = vec![tree.root_dir.clone()];
+
+        run(Config::new(), Box::new(sut)).unwrap();
+
+        assert!(!tree.exists("a/.emptydir"));
+        assert!(repo.index_files().unwrap().is_empty());
+    }
+}
+
+mod test_Convert {
+    use super::*;
+
+    #[test]
+    pub fn new() {
+        let expected = Convert {
+            dry_run: false,
+            root_dirs: Config::default_root_dirs(),
+            to: MarkerStrategy::Xattr,
+        };
+
+        assert_eq!(expected, Convert::new());
+    }
+
+    #[test]
+    pub fn execute_rejects_manifest() {
+        let ctx = TestContext::new(|_| DirDescriptorList::new());
+        let mut sut = Convert::new();
+        sut.to = MarkerStrategy::Manifest;
+
+        assert!(sut.execute(&ctx).is_err());
+    }
+}
+
+mod test_Prune {
+    use super::*;
+
+    #[test]
+    pub fn new() {
+        let expected = Prune {
+            dry_run: false,
+            root_dirs: Config::default_root_dirs(),
+        };
+
+        assert_eq!(expected, Prune::new());
+    }
+
+    #[test]
+    pub fn execute() {
+        let tree = TempTree::new("prune");
+        tree.create(&["a/b/c/", "d/.emptydir", "e/file"]);
+        let mut sut = Prune::new();
+        sut.root_dirs = vec![tree.root_dir.clone()];
+
+        run(Config::new(), Box::new(sut)).unwrap();
+
+        assert!(!tree.exists("a"));
+        assert!(tree.exists("d/.emptydir"));
+        assert!(tree.exists("e/file"));
+    }
+}
+
+mod test_Purge {
+    use super::*;
+
+    #[test]
+    pub fn new() {
+        let expected = Purge {
+            confirmation: Confirmation::Never,
+            dry_run: false,
+            max_file_count: Purge::default_max_file_count(),
+            root_dirs: Config::default_root_dirs(),
+            strict: false,
+        };
+
+        assert_eq!(expected, Purge::new());
+    }
+
+    #[test]
+    pub fn execute() {
+        let tree = TempTree::new("purge");
+        tree.create(&["a/.emptydir", "a/file", "a/b/file", "c/file"]);
+        let mut sut = Purge::new();
+        sut.root_dirs = vec![tree.root_dir.clone()];
+
+        run(Config::new(), Box::new(sut)).unwrap();
+
+        assert!(tree.exists("a/.emptydir"));
+        assert!(!tree.exists("a/file"));
+        assert!(!tree.exists("a/b"));
+        assert!(tree.exists("c/file"));
+    }
+
+    #[test]
+    pub fn execute_keeps_listed_files() {
+        let tree = TempTree::new("purge_directives");
+        tree.write("a/.emptydir", "markemptydirs: keep=README.md\n");
+        tree.create(&["a/README.md", "a/file"]);
+        let mut sut = Purge::new();
+        sut.root_dirs = vec![tree.root_dir.clone()];
+
+        run(Config::new(), Box::new(sut)).unwrap();
+
+        assert!(tree.exists("a/.emptydir"));
+        assert!(tree.exists("a/README.md"));
+        assert!(!tree.exists("a/file"));
+    }
+}
+
+mod test_Update {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    pub fn new() {
+        let expected = Update {
+            create_hook: String::new(),
+            delete_hook: String::new(),
+            dry_run: false,
+            git: false,
+            marker_text: String::new(),
+            retry_changed: false,
+            root_dirs: Config::default_root_dirs(),
+            substitute_variables: true,
+        };
+
+        assert_eq!(expected, Update::new());
+    }
+
+    #[test]
+    pub fn execute() {
+        let tree = TempTree::new("update");
+        tree.create(&["a/", "b/file"]);
+        let mut sut = Update::new();
+        sut.root_dirs = vec![tree.root_dir.clone()];
+
+        run(Config::new(), Box::new(sut)).unwrap();
+
+        assert!(tree.exists("a/.emptydir"));
+        assert!(!tree.exists("b/.emptydir"));
+    }
+
+    #[test]
+    pub fn execute_with_git() {
+        let tree = TempTree::new("update_git");
+        let repo = init_git_repo(&tree);
+        tree.create(&["a/"]);
+        let mut sut = Update::new();
+        sut.git = true;
+        sut.root_dirs = vec![tree.root_dir.clone()];
+
+        run(Config::new(), Box::new(sut)).unwrap();
+
+        assert_eq!(vec![tree.path("a/.emptydir")], repo.index_files().unwrap());
+    }
+
+    #[test]
+    pub fn execute_with_git_index_emptiness() {
+        let tree = TempTree::new("update_git_index");
+        init_git_repo(&tree);
+        tree.create(&["a/untracked", "b/tracked"]);
+        run_git(&tree.root_dir, &["add", "b/tracked"]).unwrap();
+        let mut cfg = Config::new();
+        cfg.emptiness = Emptiness::GitIndex;
+        let mut sut = Update::new();
+        sut.root_dirs = vec![tree.root_dir.clone()];
+
+        run(cfg, Box::new(sut)).unwrap();
+
+        assert!(tree.exists("a/.emptydir"));
+        assert!(!tree.exists("b/.emptydir"));
+    }
+
+    #[test]
+    pub fn execute_with_selected_paths() {
+        let tree = TempTree::new("update_selected");
+        tree.create(&["a/", "b/"]);
+        let mut cfg = Config::new();
+        // Deleted paths select their parent directory.
+        cfg.selected_paths = Some(vec![tree.path("a/deleted")]);
+        let mut sut = Update::new();
+        sut.root_dirs = vec![tree.root_dir.clone()];
+
+        run(cfg, Box::new(sut)).unwrap();
+
+        assert!(tree.exists("a/.emptydir"));
+        assert!(!tree.exists("b/.emptydir"));
+    }
+
+    #[test]
+    pub fn execute_preserving_dir_times() {
+        let tree = TempTree::new("update_times");
+        tree.create(&["a/"]);
+        let dir_mtime = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let marker_mtime = UNIX_EPOCH + Duration::from_secs(86400);
+        create_file_system_access(false)
+            .set_times(&tree.path("a"), dir_mtime, dir_mtime)
+            .unwrap();
+        let mut cfg = Config::new();
+        cfg.marker_mtime = Some(marker_mtime);
+        cfg.preserve_dir_times = true;
+        let mut sut = Update::new();
+        sut.root_dirs = vec![tree.root_dir.clone()];
+
+        run(cfg, Box::new(sut)).unwrap();
+
+        let modified = |path| tree.path(path).metadata().unwrap().modified().unwrap();
+        assert_eq!(marker_mtime, modified("a/.emptydir"));
+        assert_eq!(dir_mtime, modified("a"));
+    }
+}
+
+mod test_Restore {
+    use super::*;
+
+    fn create_config(tree: &TempTree) -> Config {
+        let mut cfg = Config::new();
+        cfg.trash = Some(TrashLocation::Directory(tree.path("trash")));
+        cfg
+    }
+
+    #[test]
+    pub fn execute_after_purge() {
+        let tree = TempTree::new("restore");
+        tree.create(&["root/a/.emptydir"]);
+        tree.write("root/a/file", "content");
+        let mut purge = Purge::new();
+        purge.root_dirs = vec![tree.path("root")];
+        run(create_config(&tree), Box::new(purge)).unwrap();
+        assert!(!tree.exists("root/a/file"));
+        let mut sut = Restore::new();
+        sut.root_dirs = vec![tree.path("root")];
+
+        run(create_config(&tree), Box::new(sut)).unwrap();
+
+        assert_eq!(
+            "content",
+            std::fs::read_to_string(tree.path("root/a/file")).unwrap()
+        );
+    }
+}
+
+mod test_Hook {
+    use super::*;
+
+    // Repository with an empty directory 'a' and a non-empty directory 'b' committed.
+    fn create_repo(name: &str) -> TempTree {
+        let tree = TempTree::new(name);
+        init_git_repo(&tree);
+        tree.create(&["a/.emptydir", "b/file"]);
+        run_git(&tree.root_dir, &["add", "."]).unwrap();
+        let identity = ["-c", "user.name=test", "-c", "user.email=test@example.com"];
+        let commit = ["commit", "-q", "-m", "Initial commit"];
+        run_git(&tree.root_dir, &[&identity[..], &commit[..]].concat()).unwrap();
+        tree
+    }
+
+    fn run_hook(tree: &TempTree) -> Result<Report> {
+        let mut sut = HookRun::new();
+        sut.repo_dir = tree.root_dir.clone();
+        run(Config::new(), Box::new(sut))
+    }
+
+    #[test]
+    pub fn new() {
+        let expected = HookRun {
+            repo_dir: PathBuf::from("."),
+        };
+
+        assert_eq!(expected, HookRun::new());
+    }
+
+    #[test]
+    pub fn install() {
+        let tree = create_repo("hook_install");
+        let mut sut = HookInstall::new();
+        sut.repo_dir = tree.root_dir.clone();
+
+        run(Config::new(), Box::new(sut)).unwrap();
+
+        let script = std::fs::read_to_string(tree.path(".git/hooks/pre-commit")).unwrap();
+        assert!(script.contains("hook run"));
+    }
+
+    #[test]
+    pub fn run_accepts_staged_markers() {
+        let tree = create_repo("hook_run");
+        tree.create(&["c/.emptydir"]);
+        run_git(&tree.root_dir, &["add", "c/.emptydir"]).unwrap();
+
+        run_hook(&tree).unwrap();
+    }
+
+    #[test]
+    pub fn run_rejects_marker_in_non_empty_dir() {
+        let tree = create_repo("hook_run_clashing");
+        tree.create(&["b/.emptydir"]);
+        run_git(&tree.root_dir, &["add", "b/.emptydir"]).unwrap();
+
+        assert!(run_hook(&tree).is_err());
+    }
+
+    #[test]
+    pub fn run_rejects_marker_removed_from_index() {
+        let tree = create_repo("hook_run_unstaged");
+        run_git(&tree.root_dir, &["rm", "-q", "--cached", "a/.emptydir"]).unwrap();
+
+        assert!(tree.exists("a/.emptydir"));
+        assert!(run_hook(&tree).is_err());
+    }
+}
+
+mod test_Variables {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    pub fn substitute() {
+        let mut cfg = Config::new();
+        cfg.source_date_epoch = Some(UNIX_EPOCH + Duration::from_secs(86400 + 3661));
+        let sut = Variables::new(&cfg, &vec![PathBuf::from("a")]);
+
+        assert_eq!(
+            "empty dir 'b' (.emptydir) since 1970-01-02 01:01:01, ${unknown} ${open",
+            sut.substitute(
+                "empty dir '${dir.name}' (${marker.name}) since ${date} ${time}, ${unknown} ${open",
+                &PathBuf::from("a/b")
+            )
+        );
+        assert_eq!(
+            to_native(&PathBuf::from("b/c")),
+            sut.substitute("${dir}", &PathBuf::from("a/b/c"))
+        );
+        assert_eq!(".", sut.substitute("${dir}", &PathBuf::from("a")));
+        assert_eq!(
+            "1970-01-02T01:01:01+00:00",
+            sut.substitute("${datetime}", &PathBuf::from("a"))
+        );
+    }
+}
+
+mod test_Report {
+    use super::*;
+
+    #[test]
+    pub fn record() {
+        let mut sut = Report::new();
+
+        sut.record(Action::DeleteMarker, &PathBuf::from("b"), Outcome::Done);
+        sut.record(
+            Action::CreateMarker,
+            &PathBuf::from("a"),
+            Outcome::Failed("denied".to_owned()),
+        );
+        sut.sort();
+
+        assert!(!sut.is_success());
+        assert_eq!(PathBuf::from("a"), sut.actions[0].path);
+        assert_eq!(PathBuf::from("b"), sut.actions[1].path);
+        assert_eq!(
+            vec![PathError {
+                path: PathBuf::from("a"),
+                message: "denied".to_owned(),
+            }],
+            sut.errors
+        );
+    }
+    #[test]
+    pub fn take_changed_dirs() {
+        let mut sut = Report::new();
+
+        sut.record(
+            Action::CreateMarker,
+            &PathBuf::from("b"),
+            Outcome::state_changed(),
+        );
+        sut.record(Action::DeleteMarker, &PathBuf::from("a"), Outcome::Done);
+        sut.record(
+            Action::DeleteMarker,
+            &PathBuf::from("c"),
+            Outcome::state_changed(),
+        );
+
+        assert_eq!(
+            vec![PathBuf::from("b"), PathBuf::from("c")],
+            sut.take_changed_dirs()
+        );
+        assert_eq!(1, sut.actions.len());
+        assert_eq!(PathBuf::from("a"), sut.actions[0].path);
+    }
 }
@@ -3,15 +3,76 @@ extern crate api;
 use api::application::*;
 use api::commands::*;
 use api::fs::*;
+use api::interaction::*;
 use api::notification::*;
 use std::sync::{Arc, Mutex};
 
+// Directory tree of its own for each test and process, removed again when dropped.
+pub struct TempTree {
+    pub root_dir: PathBuf,
+}
+
+impl TempTree {
+    pub fn new(name: &str) -> TempTree {
+        let dir =
+            std::env::temp_dir().join(format!("markemptydirs_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempTree {
+            root_dir: dir.canonicalize().unwrap(),
+        }
+    }
+
+    pub fn path(&self, path: &str) -> PathBuf {
+        self.root_dir.join(path)
+    }
+
+    // Creates the given paths, directories if they end with a slash and files otherwise.
+    pub fn create(&self, paths: &[&str]) {
+        for path in paths {
+            if path.ends_with('/') {
+                std::fs::create_dir_all(self.path(path)).unwrap();
+            } else {
+                self.write(path, "");
+            }
+        }
+    }
+
+    pub fn write(&self, path: &str, content: &str) {
+        let path = self.path(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.path(path).exists()
+    }
+}
+
+impl Drop for TempTree {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root_dir);
+    }
+}
+
+pub fn run(cfg: Config, cmd: Box<dyn Command>) -> Result<Report> {
+    Session::new(
+        ApplicationInfo::new(),
+        cfg,
+        Execution::Run(cmd),
+        LoggerNotifier::create,
+    )
+    .run()
+}
+
 pub struct TestContext {
     appinfo: ApplicationInfo,
     config: Config,
+    fsaccess: Box<dyn FileSystemAccess>,
     notifier: StdoutNotifier,
+    prompter: DefaultAnswerPrompter,
 
-    crawl_dirs_fn: Box<Fn(&PathList) -> DirDescriptorList>,
+    crawl_dirs_fn: Box<dyn Fn(&PathList) -> DirDescriptorList>,
 
     markers_created: Arc<Mutex<PathList>>,
     markers_deleted: Arc<Mutex<PathList>>,
@@ -33,10 +94,12 @@ impl TestContext {
                 version_info: VersionInfo::new_with_suffix(0, 1, 0, "beta1"),
             },
             config: Config::new(),
+            fsaccess: create_file_system_access(true),
             notifier: StdoutNotifier {
                 log_level: LogLevel::Debug,
                 message_length: MessageLength::Long,
             },
+            prompter: DefaultAnswerPrompter {},
             crawl_dirs_fn: Box::new(crawl_dirs_fn),
             markers_created: Arc::new(Mutex::new(Vec::new())),
             markers_deleted: Arc::new(Mutex::new(Vec::new())),
@@ -54,6 +117,19 @@ impl TestContext {
 
 unsafe impl Sync for TestContext {}
 
+#[derive(Debug)]
+pub struct DefaultAnswerPrompter {}
+
+impl Prompter for DefaultAnswerPrompter {
+    fn ask(&self, _question: &str, _answers: &[&str], default: usize) -> usize {
+        default
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
+
 impl std::fmt::Debug for TestContext {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
@@ -73,10 +149,18 @@ impl Context for TestContext {
         &self.config
     }
 
-    fn get_notifier(&self) -> &Notifier {
+    fn get_file_system_access(&self) -> &dyn FileSystemAccess {
+        &*self.fsaccess
+    }
+
+    fn get_notifier(&self) -> &dyn Notifier {
         &self.notifier
     }
 
+    fn get_prompter(&self) -> &dyn Prompter {
+        &self.prompter
+    }
+
     fn get_root_dir<'a>(
         &self,
         dir: &PathBuf,
@@ -90,15 +174,49 @@ impl Context for TestContext {
         (self.crawl_dirs_fn)(root_dirs)
     }
 
-    fn create_marker(&self, dir: &PathBuf, _text: &String) {
-        self.markers_created.lock().unwrap().push(dir.to_owned())
+    fn crawl_dirs_shallow(&self, dirs: &PathList) -> DirDescriptorList {
+        (self.crawl_dirs_fn)(dirs)
+    }
+
+    fn convert_marker(&self, _dir: &PathBuf, _to: MarkerStrategy) -> Outcome {
+        Outcome::Done
+    }
+
+    fn create_marker(&self, descr: &DirDescriptor, _text: &String) -> Outcome {
+        self.markers_created.lock().unwrap().push(descr.dir.clone());
+        Outcome::Done
     }
 
-    fn delete_child_file(&self, _file: &PathBuf) {}
+    fn delete_child_file(&self, _file: &PathBuf) -> Outcome {
+        Outcome::Done
+    }
+
+    fn delete_child_dir(&self, _dir: &PathBuf) -> Outcome {
+        Outcome::Done
+    }
 
-    fn delete_child_dir(&self, _dir: &PathBuf) {}
+    fn delete_empty_dir(&self, _dir: &PathBuf) -> Outcome {
+        Outcome::Done
+    }
+
+    fn delete_marker(&self, descr: &DirDescriptor, _expected: Option<DirStatus>) -> Outcome {
+        self.markers_deleted.lock().unwrap().push(descr.dir.clone());
+        Outcome::Done
+    }
+
+    fn write_manifests(&self) -> Vec<(PathBuf, Outcome)> {
+        vec![]
+    }
+
+    fn update_git_index(
+        &self,
+        _root_dirs: &PathList,
+        _marked_dirs: &PathList,
+        _unmarked_dirs: &PathList,
+    ) {
+    }
 
-    fn delete_marker(&self, dir: &PathBuf) {
-        self.markers_deleted.lock().unwrap().push(dir.to_owned())
+    fn take_retry_records(&self) -> Vec<RetryRecord> {
+        vec![]
     }
 }
@@ -0,0 +1,770 @@
+#![allow(non_snake_case)]
+
+extern crate api;
+
+use api::fs::*;
+use std::io::Write;
+
+fn write_temp_file(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    let mut file = std::fs::File::create(&path).unwrap();
+    file.write_all(content.as_bytes()).unwrap();
+    path
+}
+
+// Empty directory of its own for each test and process, removed again when dropped.
+struct TempDir(PathBuf);
+
+impl std::ops::Deref for TempDir {
+    type Target = PathBuf;
+
+    fn deref(&self) -> &PathBuf {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn create_temp_dir(name: &str) -> TempDir {
+    let dir = std::env::temp_dir().join(format!("markemptydirs_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    TempDir(dir)
+}
+
+mod test_read_path_list {
+    use super::*;
+
+    #[test]
+    fn newline_separated() {
+        let path = write_temp_file("markemptydirs_paths_nl", "a/b\r\n\nc d\n");
+
+        let expected: PathList = vec![PathBuf::from("a/b"), PathBuf::from("c d")];
+        assert_eq!(expected, read_path_list(&path, false).unwrap());
+    }
+
+    #[test]
+    fn null_separated() {
+        let path = write_temp_file("markemptydirs_paths_nul", "a/b\0line\nbreak\0");
+
+        let expected: PathList = vec![PathBuf::from("a/b"), PathBuf::from("line\nbreak")];
+        assert_eq!(expected, read_path_list(&path, true).unwrap());
+    }
+}
+
+mod test_Directives {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn parse() {
+        let sut =
+            Directives::parse("markemptydirs: delete=never purge=on-clean keep=README.md,a\ntext")
+                .unwrap();
+
+        assert_eq!(DeletePolicy::Never, sut.delete);
+        assert_eq!(PurgePolicy::OnClean, sut.purge);
+        assert!(sut.keeps(OsStr::new("README.md")));
+        assert!(sut.keeps(OsStr::new("a")));
+        assert!(!sut.keeps(OsStr::new("b")));
+    }
+
+    #[test]
+    fn parse_without_header() {
+        assert_eq!(Directives::new(), Directives::parse("some text").unwrap());
+        assert_eq!(Directives::new(), Directives::parse("").unwrap());
+    }
+
+    #[test]
+    fn sign_marker_text() {
+        let text = super::sign_marker_text("text", "markemptydirs/0.1");
+        assert_eq!("markemptydirs: signature=markemptydirs/0.1\ntext", text);
+        assert!(Directives::parse(&text)
+            .unwrap()
+            .is_signed_by("markemptydirs"));
+
+        let text = super::sign_marker_text("markemptydirs: delete=never\n", "markemptydirs/0.1");
+        let directives = Directives::parse(&text).unwrap();
+        assert!(directives.is_signed_by("markemptydirs"));
+        assert_eq!(DeletePolicy::Never, directives.delete);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(Directives::parse("markemptydirs: purge=sometimes").is_err());
+    }
+}
+
+mod test_FileSystemAccess {
+    use super::*;
+
+    #[test]
+    fn create_file_does_not_clobber() {
+        let dir = std::env::temp_dir().join("markemptydirs_create_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("file");
+        let options = FileOptions {
+            mode: Some(0o640),
+            ..FileOptions::default()
+        };
+
+        let sut = create_file_system_access(false);
+        sut.create_file(&file, "first", &options).unwrap();
+        let error = sut.create_file(&file, "second", &options).unwrap_err();
+
+        assert_eq!(std::io::ErrorKind::AlreadyExists, error.kind());
+        assert_eq!("first", std::fs::read_to_string(&file).unwrap());
+        assert_eq!(1, std::fs::read_dir(&dir).unwrap().count());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = file.metadata().unwrap().permissions().mode();
+            assert_eq!(0o640, mode & 0o7777);
+        }
+    }
+
+    #[test]
+    fn replace_file() {
+        let dir = std::env::temp_dir().join("markemptydirs_replace_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("file");
+
+        let sut = create_file_system_access(false);
+        sut.replace_file(&file, "first", &FileOptions::default())
+            .unwrap();
+        sut.replace_file(&file, "second", &FileOptions::default())
+            .unwrap();
+
+        assert_eq!("second", std::fs::read_to_string(&file).unwrap());
+        assert_eq!(1, std::fs::read_dir(&dir).unwrap().count());
+    }
+
+    #[test]
+    fn move_path_does_not_clobber() {
+        let dir = create_temp_dir("move_test");
+        let (from, to) = (dir.join("from"), dir.join("to"));
+        std::fs::write(&from, "from").unwrap();
+        std::fs::write(&to, "to").unwrap();
+
+        let sut = create_file_system_access(false);
+        let error = sut.move_path(&from, &to).unwrap_err();
+
+        assert_eq!(std::io::ErrorKind::AlreadyExists, error.kind());
+        assert_eq!("from", std::fs::read_to_string(&from).unwrap());
+        assert_eq!("to", std::fs::read_to_string(&to).unwrap());
+    }
+
+    #[test]
+    fn move_path_does_not_replace_empty_dirs() {
+        let dir = create_temp_dir("move_dir_test");
+        let (from, to) = (dir.join("from"), dir.join("to"));
+        std::fs::create_dir_all(from.join("a")).unwrap();
+        std::fs::create_dir(&to).unwrap();
+
+        let sut = create_file_system_access(false);
+        let error = sut.move_path(&from, &to).unwrap_err();
+        assert_eq!(std::io::ErrorKind::AlreadyExists, error.kind());
+
+        std::fs::remove_dir(&to).unwrap();
+        sut.move_path(&from, &to).unwrap();
+        assert!(to.join("a").is_dir());
+        assert!(!from.exists());
+    }
+
+    #[test]
+    fn recognizes_temp_file_names() {
+        use std::ffi::OsStr;
+        assert!(is_temp_file_name(OsStr::new(".emptydir.123.0.tmp")));
+        assert!(is_temp_file_name(OsStr::new("..emptydirs.1.22.tmp")));
+        assert!(!is_temp_file_name(OsStr::new(".emptydir.tmp")));
+        assert!(!is_temp_file_name(OsStr::new(".emptydir.x.0.tmp")));
+        assert!(!is_temp_file_name(OsStr::new("..1.0.tmp")));
+        assert!(!is_temp_file_name(OsStr::new("file.1.0.tmp")));
+    }
+}
+
+mod test_FileSystemCrawler {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn get_current_state() {
+        let root_dir = std::env::temp_dir().join("markemptydirs_current_state");
+        let _ = std::fs::remove_dir_all(&root_dir);
+        std::fs::create_dir_all(&root_dir).unwrap();
+        let sut = FileSystemCrawler {
+            exclude_dirs: vec![],
+            dereference_symlinks: false,
+            emptiness: Emptiness::FileSystem,
+            manifest_dirs: HashSet::new(),
+            manifest_name: ".emptydirs".to_owned(),
+            marker_name: ".emptydir".to_owned(),
+            marker_strategy: MarkerStrategy::File,
+            read_marker_xattrs: false,
+            read_throttle: std::sync::Arc::new(Throttle::new(ThrottlePolicy::new())),
+            retry_log: std::sync::Arc::new(RetryLog::default()),
+            retry_policy: RetryPolicy::new(),
+        };
+        let descr = sut
+            .crawl_dirs_shallow(vec![root_dir.clone()])
+            .into_iter()
+            .next()
+            .unwrap()
+            .1;
+        assert_eq!((false, false), sut.get_current_state(&descr).unwrap());
+
+        std::fs::write(root_dir.join(".emptydir"), "").unwrap();
+        assert_eq!((true, false), sut.get_current_state(&descr).unwrap());
+
+        std::fs::write(root_dir.join(".emptydir.123.0.tmp"), "").unwrap();
+        assert_eq!((true, false), sut.get_current_state(&descr).unwrap());
+
+        std::fs::write(root_dir.join("file"), "").unwrap();
+        assert_eq!((true, true), sut.get_current_state(&descr).unwrap());
+    }
+
+    #[test]
+    fn crawl_dirs_ignores_temp_files() {
+        let root_dir = create_temp_dir("crawl_temp_files");
+        std::fs::write(root_dir.join(".emptydir.123.0.tmp"), "").unwrap();
+        let sut = FileSystemCrawler {
+            exclude_dirs: vec![],
+            dereference_symlinks: false,
+            emptiness: Emptiness::FileSystem,
+            manifest_dirs: HashSet::new(),
+            manifest_name: ".emptydirs".to_owned(),
+            marker_name: ".emptydir".to_owned(),
+            marker_strategy: MarkerStrategy::File,
+            read_marker_xattrs: false,
+            read_throttle: std::sync::Arc::new(Throttle::new(ThrottlePolicy::new())),
+            retry_log: std::sync::Arc::new(RetryLog::default()),
+            retry_policy: RetryPolicy::new(),
+        };
+
+        let descr = sut
+            .crawl_dirs(vec![root_dir.clone()])
+            .into_iter()
+            .next()
+            .unwrap()
+            .1;
+
+        assert!(!descr.has_marker());
+        assert!(!descr.has_children());
+    }
+}
+
+mod test_RootLock {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn acquire() {
+        let root_dir = create_temp_dir("lock");
+
+        let lock = RootLock::acquire(&root_dir, Duration::from_secs(0), |_| ()).unwrap();
+        let mut waited_for = String::new();
+        let error = RootLock::acquire(&root_dir, Duration::from_millis(200), |holder| {
+            waited_for = holder.to_owned()
+        })
+        .unwrap_err();
+        assert_eq!(std::io::ErrorKind::WouldBlock, error.kind());
+        let process = format!("process {}", std::process::id());
+        assert!(waited_for.starts_with(&process));
+        assert!(error.to_string().contains(&process));
+
+        drop(lock);
+        assert!(RootLock::acquire(&root_dir, Duration::from_secs(0), |_| ()).is_ok());
+    }
+
+    #[test]
+    fn acquire_nested() {
+        let root_dir = create_temp_dir("lock_nested");
+        let (dir_a, dir_b) = (root_dir.join("a"), root_dir.join("b"));
+        std::fs::create_dir_all(&dir_a).unwrap();
+        std::fs::create_dir_all(&dir_b).unwrap();
+
+        let lock = RootLock::acquire(&dir_a, Duration::from_secs(0), |_| ()).unwrap();
+        assert!(RootLock::acquire(&dir_b, Duration::from_secs(0), |_| ()).is_ok());
+        let error = RootLock::acquire(&root_dir, Duration::from_secs(0), |_| ()).unwrap_err();
+        assert_eq!("locked by another process", error.to_string());
+        drop(lock);
+
+        let lock = RootLock::acquire(&root_dir, Duration::from_secs(0), |_| ()).unwrap();
+        assert!(RootLock::acquire(&dir_a, Duration::from_secs(0), |_| ()).is_err());
+        drop(lock);
+    }
+}
+
+mod test_RetryPolicy {
+    use super::*;
+    use std::io::{Error, ErrorKind};
+    use std::time::Duration;
+
+    fn create_policy() -> RetryPolicy {
+        let mut policy = RetryPolicy::new();
+        policy.initial_delay = Duration::from_millis(1);
+        policy
+    }
+
+    #[test]
+    fn run_retries_transient_errors() {
+        let log = RetryLog::default();
+        let mut attempts = 0;
+        let result = create_policy().run(&log, "remove_file", Path::new("a"), || {
+            attempts += 1;
+            match attempts {
+                1 | 2 => Err(Error::new(ErrorKind::Interrupted, "interrupted")),
+                _ => Ok(attempts),
+            }
+        });
+        assert_eq!(3, result.unwrap());
+        let records = log.take();
+        assert_eq!(1, records.len());
+        assert_eq!("remove_file", records[0].operation);
+        assert_eq!(2, records[0].retries);
+        assert_eq!("interrupted", records[0].error);
+        assert!(records[0].succeeded);
+    }
+
+    #[test]
+    fn run_does_not_retry_other_errors() {
+        let log = RetryLog::default();
+        let mut attempts = 0;
+        let result: std::io::Result<()> =
+            create_policy().run(&log, "remove_file", Path::new("a"), || {
+                attempts += 1;
+                Err(Error::new(ErrorKind::PermissionDenied, "denied"))
+            });
+        assert!(result.is_err());
+        assert_eq!(1, attempts);
+        assert!(log.take().is_empty());
+    }
+
+    #[test]
+    fn run_gives_up_after_max_retries() {
+        let log = RetryLog::default();
+        let mut policy = create_policy();
+        policy.max_retries = 2;
+        let mut attempts = 0;
+        let result: std::io::Result<()> = policy.run(&log, "read_dir", Path::new("a"), || {
+            attempts += 1;
+            Err(Error::new(ErrorKind::TimedOut, "timed out"))
+        });
+        assert_eq!(ErrorKind::TimedOut, result.unwrap_err().kind());
+        assert_eq!(3, attempts);
+        let records = log.take();
+        assert_eq!(1, records.len());
+        assert_eq!(2, records[0].retries);
+        assert!(!records[0].succeeded);
+    }
+
+    #[test]
+    fn run_retries_only_selected_errors() {
+        let log = RetryLog::default();
+        let mut policy = create_policy();
+        policy.retried_errors = vec![TransientError::Busy];
+        let mut attempts = 0;
+        let result: std::io::Result<()> = policy.run(&log, "read_dir", Path::new("a"), || {
+            attempts += 1;
+            Err(Error::new(ErrorKind::TimedOut, "timed out"))
+        });
+        assert!(result.is_err());
+        assert_eq!(1, attempts);
+    }
+}
+
+mod test_RetryingFileSystemAccess {
+    use super::*;
+    use std::io::{Error, ErrorKind, Result};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    // Reports an interruption after the first operation succeeded nonetheless.
+    #[derive(Debug)]
+    struct InterruptedOnceFileSystemAccess {
+        inner: Box<dyn FileSystemAccess>,
+        interrupted: AtomicBool,
+    }
+
+    impl InterruptedOnceFileSystemAccess {
+        fn run(&self, result: Result<()>) -> Result<()> {
+            result?;
+            if self.interrupted.swap(true, Ordering::SeqCst) {
+                Ok(())
+            } else {
+                Err(Error::new(ErrorKind::Interrupted, "interrupted"))
+            }
+        }
+    }
+
+    impl FileSystemAccess for InterruptedOnceFileSystemAccess {
+        fn create_dir_all(&self, path: &Path) -> Result<()> {
+            self.run(self.inner.create_dir_all(path))
+        }
+
+        fn create_file(&self, path: &Path, text: &str, options: &FileOptions) -> Result<()> {
+            self.run(self.inner.create_file(path, text, options))
+        }
+
+        fn replace_file(&self, path: &Path, text: &str, options: &FileOptions) -> Result<()> {
+            self.run(self.inner.replace_file(path, text, options))
+        }
+
+        fn move_path(&self, from: &Path, to: &Path) -> Result<()> {
+            self.run(self.inner.move_path(from, to))
+        }
+
+        fn remove_dir(&self, path: &Path) -> Result<()> {
+            self.run(self.inner.remove_dir(path))
+        }
+
+        fn remove_file(&self, path: &Path) -> Result<()> {
+            self.run(self.inner.remove_file(path))
+        }
+
+        fn remove_dir_all(&self, path: &Path) -> Result<()> {
+            self.run(self.inner.remove_dir_all(path))
+        }
+
+        fn remove_xattr(&self, path: &Path, name: &str) -> Result<()> {
+            self.run(self.inner.remove_xattr(path, name))
+        }
+
+        fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+            self.run(self.inner.set_permissions(path, mode))
+        }
+
+        fn set_times(&self, path: &Path, atime: SystemTime, mtime: SystemTime) -> Result<()> {
+            self.run(self.inner.set_times(path, atime, mtime))
+        }
+
+        fn set_xattr(&self, path: &Path, name: &str, value: &str) -> Result<()> {
+            self.run(self.inner.set_xattr(path, name, value))
+        }
+    }
+
+    fn create_sut() -> RetryingFileSystemAccess {
+        let mut policy = RetryPolicy::new();
+        policy.initial_delay = Duration::from_millis(1);
+        RetryingFileSystemAccess::new(
+            Box::new(InterruptedOnceFileSystemAccess {
+                inner: create_file_system_access(false),
+                interrupted: AtomicBool::new(false),
+            }),
+            policy,
+            Arc::new(RetryLog::default()),
+        )
+    }
+
+    #[test]
+    fn create_file_confirms_earlier_attempt() {
+        let dir = create_temp_dir("retry_create_test");
+        let file = dir.join("file");
+
+        create_sut()
+            .create_file(&file, "text", &FileOptions::default())
+            .unwrap();
+
+        assert_eq!("text", std::fs::read_to_string(&file).unwrap());
+    }
+
+    #[test]
+    fn create_file_fails_for_other_content() {
+        let dir = create_temp_dir("retry_create_other_test");
+        let file = dir.join("file");
+        std::fs::write(&file, "other").unwrap();
+
+        let error = create_sut()
+            .create_file(&file, "text", &FileOptions::default())
+            .unwrap_err();
+
+        assert_eq!(ErrorKind::AlreadyExists, error.kind());
+    }
+
+    #[test]
+    fn move_path_confirms_earlier_attempt() {
+        let dir = create_temp_dir("retry_move_test");
+        let (from, to) = (dir.join("from"), dir.join("to"));
+        std::fs::write(&from, "").unwrap();
+
+        create_sut().move_path(&from, &to).unwrap();
+
+        assert!(!from.exists());
+        assert!(to.exists());
+    }
+
+    #[test]
+    fn remove_file_confirms_earlier_attempt() {
+        let dir = create_temp_dir("retry_remove_test");
+        let file = dir.join("file");
+        std::fs::write(&file, "").unwrap();
+
+        create_sut().remove_file(&file).unwrap();
+
+        assert!(!file.exists());
+    }
+}
+
+mod test_Throttle {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn run_limits_concurrent_ops() {
+        let mut policy = ThrottlePolicy::new();
+        policy.max_concurrent_ops = Some(2);
+        let sut = Throttle::new(policy);
+        let running_ops = AtomicUsize::new(0);
+        let max_running_ops = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..6 {
+                scope.spawn(|| {
+                    sut.run(|| {
+                        let running = running_ops.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_running_ops.fetch_max(running, Ordering::SeqCst);
+                        std::thread::sleep(Duration::from_millis(20));
+                        running_ops.fetch_sub(1, Ordering::SeqCst);
+                    })
+                });
+            }
+        });
+        assert_eq!(2, max_running_ops.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn run_limits_ops_per_sec() {
+        let mut policy = ThrottlePolicy::new();
+        policy.max_ops_per_sec = Some(50);
+        let sut = Throttle::new(policy);
+        let start = Instant::now();
+        for _ in 0..5 {
+            sut.run(|| ());
+        }
+        // The first operation starts right away, each further one 20ms after the previous.
+        assert!(start.elapsed() >= Duration::from_millis(80));
+    }
+}
+
+mod test_Manifest {
+    use super::*;
+
+    #[test]
+    fn read_and_write() {
+        let dir = std::env::temp_dir().join("markemptydirs_manifest_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        write_temp_file(
+            "markemptydirs_manifest_test/.emptydirs",
+            "# comment\n\nb/c\n./a\n.\n",
+        );
+
+        let mut sut = Manifest::read(&dir, ".emptydirs").unwrap();
+        assert!(sut.contains(&dir));
+        assert!(sut.contains(&dir.join("a")));
+        assert!(!sut.is_changed());
+
+        assert!(sut.insert(&dir.join("d")).unwrap());
+        assert!(!sut.insert(&dir.join("a")).unwrap());
+        assert!(sut.remove(&dir).unwrap());
+        assert!(sut.is_changed());
+
+        sut.write(&*create_file_system_access(false)).unwrap();
+        assert_eq!(
+            "# Empty directories recorded by markemptydirs\na\nb/c\nd\n",
+            std::fs::read_to_string(dir.join(".emptydirs")).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_entries_outside_of_root_dir() {
+        let dir = std::env::temp_dir().join("markemptydirs_manifest_invalid_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        write_temp_file(
+            "markemptydirs_manifest_invalid_test/.emptydirs",
+            "a/../../b\n",
+        );
+
+        assert!(Manifest::read(&dir, ".emptydirs").is_err());
+    }
+}
+
+mod test_xattr {
+    use super::*;
+
+    #[test]
+    fn set_get_and_remove() {
+        let dir = std::env::temp_dir().join("markemptydirs_xattr_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let sut = create_file_system_access(false);
+        if let Err(error) = sut.set_xattr(&dir, MARKER_XATTR_NAME, "text") {
+            // The file system does not support user attributes.
+            eprintln!("{}", error);
+            return;
+        }
+
+        assert_eq!(
+            Some(b"text".to_vec()),
+            get_xattr(&dir, MARKER_XATTR_NAME).unwrap()
+        );
+        sut.remove_xattr(&dir, MARKER_XATTR_NAME).unwrap();
+        assert_eq!(None, get_xattr(&dir, MARKER_XATTR_NAME).unwrap());
+    }
+}
+
+mod test_safety {
+    use super::*;
+
+    #[test]
+    fn is_dangerous_root_dir() {
+        assert!(super::is_dangerous_root_dir(Path::new("/")));
+        assert!(super::is_dangerous_root_dir(Path::new("/usr")));
+        assert!(!super::is_dangerous_root_dir(
+            &std::env::temp_dir().join(".")
+        ));
+        if let Some(home_dir) = std::env::var_os("HOME") {
+            assert!(super::is_dangerous_root_dir(Path::new(&home_dir)));
+        }
+    }
+
+    #[test]
+    fn validate_file_name() {
+        assert!(super::validate_file_name(".emptydir").is_ok());
+        assert!(super::validate_file_name("").is_err());
+        assert!(super::validate_file_name(".").is_err());
+        assert!(super::validate_file_name("..").is_err());
+        assert!(super::validate_file_name("a/b").is_err());
+        assert!(super::validate_file_name("/a").is_err());
+        assert!(super::validate_file_name("a\\b").is_err());
+    }
+
+    #[test]
+    fn check_inside_root_dirs() {
+        let root_dir = std::env::temp_dir().join("markemptydirs_safety");
+        let _ = std::fs::remove_dir_all(&root_dir);
+        std::fs::create_dir_all(root_dir.join("a")).unwrap();
+        let root_dirs = vec![root_dir.canonicalize().unwrap()];
+
+        assert!(super::check_inside_root_dirs(&root_dirs, &root_dir.join("a")).is_ok());
+        assert!(super::check_inside_root_dirs(&root_dirs, &root_dir.join("a/b/c")).is_ok());
+        assert!(super::check_inside_root_dirs(&root_dirs, &root_dir.join("a/../..")).is_err());
+        assert!(super::check_inside_root_dirs(&root_dirs, &std::env::temp_dir()).is_err());
+
+        #[cfg(unix)]
+        {
+            let link = root_dir.join("link");
+            std::os::unix::fs::symlink(std::env::temp_dir(), &link).unwrap();
+            // The symlink itself may be removed, but not be followed.
+            assert!(super::check_inside_root_dirs(&root_dirs, &link).is_ok());
+            assert!(super::check_inside_root_dirs(&root_dirs, &link.join("a")).is_err());
+        }
+    }
+}
+
+mod test_Trash {
+    use super::*;
+
+    #[test]
+    fn put_and_restore() {
+        let base_dir = std::env::temp_dir().join("markemptydirs_trash_test");
+        let _ = std::fs::remove_dir_all(&base_dir);
+        let file = base_dir.join("data").join("file name");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        write_temp_file("markemptydirs_trash_test/data/file name", "content");
+
+        let fsaccess = create_file_system_access(false);
+        let sut = Trash::new(TrashLocation::Directory(base_dir.join("trash")));
+
+        sut.put(&*fsaccess, &file).unwrap();
+        assert!(!file.exists());
+
+        let entries = sut.get_entries().unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(file, entries[0].original_path);
+
+        sut.restore(&*fsaccess, &entries[0]).unwrap();
+        sut.remove_empty_trash_dirs(&*fsaccess).unwrap();
+        assert_eq!("content", std::fs::read_to_string(&file).unwrap());
+        assert_eq!(0, sut.get_entries().unwrap().len());
+    }
+}
+
+mod test_Trash_planning {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn put_reserves_names() {
+        let base_dir = create_temp_dir("trash_planning_test");
+        for dir in &["one", "two"] {
+            std::fs::create_dir_all(base_dir.join(dir)).unwrap();
+            std::fs::write(base_dir.join(dir).join("x"), dir).unwrap();
+        }
+        let plan = Arc::new(Mutex::new(Plan::default()));
+        let fsaccess = PlanningFileSystemAccess::new(Arc::clone(&plan));
+        let sut = Trash::new(TrashLocation::Directory(base_dir.join("trash")));
+
+        sut.put(&fsaccess, &base_dir.join("one").join("x")).unwrap();
+        sut.put(&fsaccess, &base_dir.join("two").join("x")).unwrap();
+
+        let targets: Vec<_> = plan
+            .lock()
+            .unwrap()
+            .steps
+            .iter()
+            .filter_map(|step| match step.operation {
+                PlannedOperation::MovePath { ref to, .. } => Some(to.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(2, targets.len());
+        assert_ne!(targets[0], targets[1]);
+    }
+}
+
+mod test_Plan {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn record_write_and_read() {
+        let plan = Arc::new(Mutex::new(Plan::default()));
+        let fsaccess = PlanningFileSystemAccess::new(Arc::clone(&plan));
+        let dir = create_temp_dir("plan_test");
+        let missing_file = dir.join("missing");
+
+        fsaccess
+            .create_file(&missing_file, "text", &FileOptions::default())
+            .unwrap();
+        assert!(!missing_file.exists());
+
+        let plan_file = std::env::temp_dir().join("markemptydirs_plan.json");
+        plan.lock().unwrap().write(&plan_file).unwrap();
+        let plan = Plan::read(&plan_file).unwrap();
+
+        assert_eq!(1, plan.steps.len());
+        assert_eq!(
+            PlannedOperation::CreateFile {
+                path: missing_file.clone(),
+                text: "text".to_owned(),
+                options: FileOptions::default(),
+            },
+            plan.steps[0].operation
+        );
+        assert!(!plan.steps[0].precondition.exists);
+        assert!(plan.steps[0].precondition.is_met(&missing_file));
+        std::fs::write(&missing_file, "text").unwrap();
+        assert!(!plan.steps[0].precondition.is_met(&missing_file));
+        assert_eq!(None, plan.steps[0].operation.get_modified_path());
+    }
+}
@@ -0,0 +1,56 @@
+#![allow(non_snake_case)]
+
+extern crate api;
+
+use api::notification::*;
+use api::Error;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+struct RecordingNotifier {
+    data: Arc<Mutex<Vec<String>>>,
+}
+
+impl Notifier for RecordingNotifier {
+    fn get_log_level(&self) -> LogLevel {
+        LogLevel::Info
+    }
+
+    fn notify(
+        &self,
+        _log_level: LogLevel,
+        _target: &str,
+        info: &str,
+        data: &str,
+        _error: Option<Error>,
+    ) {
+        self.data
+            .lock()
+            .unwrap()
+            .push(format!("{}: {}", info, data));
+    }
+}
+
+mod test_BufferedNotifier {
+    use super::*;
+
+    #[test]
+    fn flush_in_path_order() {
+        let data = Arc::new(Mutex::new(vec![]));
+        let sut = BufferedNotifier::create(Box::new(RecordingNotifier {
+            data: Arc::clone(&data),
+        }));
+
+        sut.info("update", "Created", "b", None);
+        sut.info("update", "Created", "a/c", None);
+        sut.debug("update", "Ignored", "a", None);
+        sut.info("update", "Staged", "b", None);
+        assert!(data.lock().unwrap().is_empty());
+
+        sut.flush();
+        assert_eq!(
+            vec!["Created: a/c", "Created: b", "Staged: b"],
+            *data.lock().unwrap()
+        );
+    }
+}
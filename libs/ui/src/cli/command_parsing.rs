@@ -1,25 +1,68 @@
-use super::CommandParser;
+use super::environment as env;
+use super::{CommandParser, Completions, ManPage};
 use api::commands;
+use api::fs::MarkerStrategy;
 use clap::ArgMatches;
 use std::path::PathBuf;
+use std::time::Duration;
 
 // Import enum values directly into this namespace in order to make code more readable,
 use api::commands::Execution::*;
 
-impl CommandParser for commands::Command {
+impl CommandParser for dyn commands::Command {
     fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
+        if let Some(cmd) = commands::Apply::parse(matches) {
+            return Some(cmd);
+        }
+
         if let Some(cmd) = commands::Clean::parse(matches) {
             return Some(cmd);
         }
 
+        if let Some(cmd) = Completions::parse(matches) {
+            return Some(cmd);
+        }
+
+        if let Some(cmd) = commands::Convert::parse(matches) {
+            return Some(cmd);
+        }
+
+        if let Some(cmd) = commands::HookInstall::parse(matches) {
+            return Some(cmd);
+        }
+
+        if let Some(cmd) = commands::HookRun::parse(matches) {
+            return Some(cmd);
+        }
+
         if let Some(cmd) = commands::List::parse(matches) {
             return Some(cmd);
         }
 
+        if let Some(cmd) = commands::Materialize::parse(matches) {
+            return Some(cmd);
+        }
+
+        if let Some(cmd) = ManPage::parse(matches) {
+            return Some(cmd);
+        }
+
+        if let Some(cmd) = commands::Prune::parse(matches) {
+            return Some(cmd);
+        }
+
         if let Some(cmd) = commands::Purge::parse(matches) {
             return Some(cmd);
         }
 
+        if let Some(cmd) = commands::Restore::parse(matches) {
+            return Some(cmd);
+        }
+
+        if let Some(cmd) = commands::TrashEmpty::parse(matches) {
+            return Some(cmd);
+        }
+
         if let Some(cmd) = commands::Update::parse(matches) {
             return Some(cmd);
         }
@@ -28,22 +71,54 @@ impl CommandParser for commands::Command {
     }
 }
 
+impl CommandParser for commands::Apply {
+    fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
+        if let ("apply", Some(matches)) = matches.subcommand() {
+            let mut cmd = Box::new(commands::Apply::new());
+
+            cmd.dry_run = matches.is_present("dry-run");
+
+            if let Some(plan_file) = matches.value_of("plan-file") {
+                cmd.plan_file = PathBuf::from(plan_file);
+            }
+
+            if cmd.dry_run {
+                Some(DryRun(cmd))
+            } else {
+                Some(Run(cmd))
+            }
+        } else {
+            None
+        }
+    }
+}
+
 impl CommandParser for commands::Clean {
     fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
-        if let ("clean", Some(ref matches)) = matches.subcommand() {
+        if let ("clean", Some(matches)) = matches.subcommand() {
             let mut cmd = Box::new(commands::Clean::new());
 
-            if let Some(delete_hook) = matches.value_of("delete-hook") {
-                cmd.delete_hook = delete_hook.to_owned();
+            cmd.confirmation = parse_confirmation(matches);
+
+            if let Some(delete_hook) =
+                env::value_of(matches, "delete-hook", "MARKEMPTYDIRS_DELETE_HOOK")
+            {
+                cmd.delete_hook = delete_hook;
             }
 
             cmd.dry_run = matches.is_present("dry-run");
 
+            cmd.git = env::is_present(matches, "git", "MARKEMPTYDIRS_GIT");
+
             if let Some(root_dirs) = matches.values_of("root-dirs") {
                 cmd.root_dirs = root_dirs.into_iter().map(PathBuf::from).collect();
             }
 
-            if cmd.dry_run {
+            cmd.strict = env::is_present(matches, "strict", "MARKEMPTYDIRS_STRICT");
+
+            if let Some(plan_file) = matches.value_of("plan-out") {
+                Some(Plan(cmd, PathBuf::from(plan_file)))
+            } else if cmd.dry_run {
                 Some(DryRun(cmd))
             } else {
                 Some(Run(cmd))
@@ -54,9 +129,75 @@ impl CommandParser for commands::Clean {
     }
 }
 
+impl CommandParser for commands::Convert {
+    fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
+        if let ("convert", Some(matches)) = matches.subcommand() {
+            let mut cmd = Box::new(commands::Convert::new());
+
+            cmd.dry_run = matches.is_present("dry-run");
+
+            if let Some(root_dirs) = matches.values_of("root-dirs") {
+                cmd.root_dirs = root_dirs.into_iter().map(PathBuf::from).collect();
+            }
+
+            cmd.to = match matches.value_of("to") {
+                Some("file") => MarkerStrategy::File,
+                _ => MarkerStrategy::Xattr,
+            };
+
+            if let Some(plan_file) = matches.value_of("plan-out") {
+                Some(Plan(cmd, PathBuf::from(plan_file)))
+            } else if cmd.dry_run {
+                Some(DryRun(cmd))
+            } else {
+                Some(Run(cmd))
+            }
+        } else {
+            None
+        }
+    }
+}
+
+impl CommandParser for commands::HookInstall {
+    fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
+        if let ("hook", Some(matches)) = matches.subcommand() {
+            let repo_dir = matches.value_of("repo-dir");
+            if let ("install", Some(matches)) = matches.subcommand() {
+                let mut cmd = Box::new(commands::HookInstall::new());
+
+                cmd.force = matches.is_present("force");
+
+                if let Some(repo_dir) = repo_dir {
+                    cmd.repo_dir = PathBuf::from(repo_dir);
+                }
+
+                return Some(Run(cmd));
+            }
+        }
+        None
+    }
+}
+
+impl CommandParser for commands::HookRun {
+    fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
+        if let ("hook", Some(matches)) = matches.subcommand() {
+            if let ("run", Some(_)) = matches.subcommand() {
+                let mut cmd = Box::new(commands::HookRun::new());
+
+                if let Some(repo_dir) = matches.value_of("repo-dir") {
+                    cmd.repo_dir = PathBuf::from(repo_dir);
+                }
+
+                return Some(Run(cmd));
+            }
+        }
+        None
+    }
+}
+
 impl CommandParser for commands::List {
     fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
-        if let ("list", Some(ref matches)) = matches.subcommand() {
+        if let ("list", Some(matches)) = matches.subcommand() {
             let mut cmd = Box::new(commands::List::new());
 
             if let Some(filter) = matches.values_of("filter") {
@@ -82,11 +223,99 @@ impl CommandParser for commands::List {
     }
 }
 
+impl CommandParser for commands::Materialize {
+    fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
+        if let ("materialize", Some(matches)) = matches.subcommand() {
+            let mut cmd = Box::new(commands::Materialize::new());
+
+            cmd.dry_run = matches.is_present("dry-run");
+
+            if let Some(root_dirs) = matches.values_of("root-dirs") {
+                cmd.root_dirs = root_dirs.into_iter().map(PathBuf::from).collect();
+            }
+
+            if cmd.dry_run {
+                Some(DryRun(cmd))
+            } else {
+                Some(Run(cmd))
+            }
+        } else {
+            None
+        }
+    }
+}
+
+impl CommandParser for commands::Prune {
+    fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
+        if let ("prune", Some(matches)) = matches.subcommand() {
+            let mut cmd = Box::new(commands::Prune::new());
+
+            cmd.dry_run = matches.is_present("dry-run");
+
+            if let Some(root_dirs) = matches.values_of("root-dirs") {
+                cmd.root_dirs = root_dirs.into_iter().map(PathBuf::from).collect();
+            }
+
+            if let Some(plan_file) = matches.value_of("plan-out") {
+                Some(Plan(cmd, PathBuf::from(plan_file)))
+            } else if cmd.dry_run {
+                Some(DryRun(cmd))
+            } else {
+                Some(Run(cmd))
+            }
+        } else {
+            None
+        }
+    }
+}
+
 impl CommandParser for commands::Purge {
     fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
-        if let ("purge", Some(ref matches)) = matches.subcommand() {
+        if let ("purge", Some(matches)) = matches.subcommand() {
             let mut cmd = Box::new(commands::Purge::new());
 
+            cmd.confirmation = parse_confirmation(matches);
+
+            cmd.dry_run = matches.is_present("dry-run");
+
+            if let Some(max_file_count) = matches.value_of("max-files") {
+                match max_file_count.parse() {
+                    Ok(max_file_count) => cmd.max_file_count = Some(max_file_count),
+                    Err(_) => {
+                        eprintln!("Invalid number of files: {}", max_file_count);
+                        return None;
+                    }
+                }
+            }
+
+            if matches.is_present("no-file-limit") {
+                cmd.max_file_count = None;
+            }
+
+            if let Some(root_dirs) = matches.values_of("root-dirs") {
+                cmd.root_dirs = root_dirs.into_iter().map(PathBuf::from).collect();
+            }
+
+            cmd.strict = env::is_present(matches, "strict", "MARKEMPTYDIRS_STRICT");
+
+            if let Some(plan_file) = matches.value_of("plan-out") {
+                Some(Plan(cmd, PathBuf::from(plan_file)))
+            } else if cmd.dry_run {
+                Some(DryRun(cmd))
+            } else {
+                Some(Run(cmd))
+            }
+        } else {
+            None
+        }
+    }
+}
+
+impl CommandParser for commands::Restore {
+    fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
+        if let ("restore", Some(matches)) = matches.subcommand() {
+            let mut cmd = Box::new(commands::Restore::new());
+
             cmd.dry_run = matches.is_present("dry-run");
 
             if let Some(root_dirs) = matches.values_of("root-dirs") {
@@ -104,32 +333,75 @@ impl CommandParser for commands::Purge {
     }
 }
 
+impl CommandParser for commands::TrashEmpty {
+    fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
+        if let ("trash", Some(matches)) = matches.subcommand() {
+            if let ("empty", Some(matches)) = matches.subcommand() {
+                let mut cmd = Box::new(commands::TrashEmpty::new());
+
+                cmd.dry_run = matches.is_present("dry-run");
+
+                if let Some(older_than) = matches.value_of("older-than") {
+                    match parse_duration(older_than) {
+                        Some(older_than) => cmd.older_than = older_than,
+                        None => {
+                            eprintln!("Invalid age: {}", older_than);
+                            return None;
+                        }
+                    }
+                }
+
+                return if cmd.dry_run {
+                    Some(DryRun(cmd))
+                } else {
+                    Some(Run(cmd))
+                };
+            }
+        }
+        None
+    }
+}
+
 impl CommandParser for commands::Update {
     fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
-        if let ("update", Some(ref matches)) = matches.subcommand() {
+        if let ("update", Some(matches)) = matches.subcommand() {
             let mut cmd = Box::new(commands::Update::new());
 
-            if let Some(create_hook) = matches.value_of("create-hook") {
-                cmd.create_hook = create_hook.to_owned();
+            if let Some(create_hook) =
+                env::value_of(matches, "create-hook", "MARKEMPTYDIRS_CREATE_HOOK")
+            {
+                cmd.create_hook = create_hook;
             }
 
-            if let Some(delete_hook) = matches.value_of("delete-hook") {
-                cmd.delete_hook = delete_hook.to_owned();
+            if let Some(delete_hook) =
+                env::value_of(matches, "delete-hook", "MARKEMPTYDIRS_DELETE_HOOK")
+            {
+                cmd.delete_hook = delete_hook;
             }
 
             cmd.dry_run = matches.is_present("dry-run");
 
-            if let Some(marker_text) = matches.value_of("marker-text") {
-                cmd.marker_text = marker_text.to_owned();
+            cmd.git = env::is_present(matches, "git", "MARKEMPTYDIRS_GIT");
+
+            if let Some(marker_text) =
+                env::value_of(matches, "marker-text", "MARKEMPTYDIRS_MARKER_TEXT")
+            {
+                cmd.marker_text = marker_text;
             }
 
+            cmd.retry_changed =
+                env::is_present(matches, "retry-changed", "MARKEMPTYDIRS_RETRY_CHANGED");
+
             if let Some(root_dirs) = matches.values_of("root-dirs") {
                 cmd.root_dirs = root_dirs.into_iter().map(PathBuf::from).collect();
             }
 
-            cmd.substitute_variables = matches.is_present("substitute-variables");
+            cmd.substitute_variables =
+                env::is_present(matches, "substitute-variables", "MARKEMPTYDIRS_SUBST_VARS");
 
-            if cmd.dry_run {
+            if let Some(plan_file) = matches.value_of("plan-out") {
+                Some(Plan(cmd, PathBuf::from(plan_file)))
+            } else if cmd.dry_run {
                 Some(DryRun(cmd))
             } else {
                 Some(Run(cmd))
@@ -139,3 +411,51 @@ impl CommandParser for commands::Update {
         }
     }
 }
+
+fn parse_confirmation(matches: &ArgMatches) -> commands::Confirmation {
+    if matches.is_present("yes") {
+        commands::Confirmation::Never
+    } else if matches.is_present("interactive") {
+        commands::Confirmation::PerDirectory
+    } else if matches.is_present("confirm") {
+        commands::Confirmation::Once
+    } else {
+        commands::Confirmation::Never
+    }
+}
+
+fn parse_duration(text: &str) -> Option<Duration> {
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => text.split_at(index),
+        None => (text, "d"),
+    };
+    let seconds_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    let number: u64 = number.parse().ok()?;
+    Some(Duration::from_secs(number.checked_mul(seconds_per_unit)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_with_units() {
+        assert_eq!(Some(Duration::from_secs(90)), parse_duration("90s"));
+        assert_eq!(Some(Duration::from_secs(2 * 60 * 60)), parse_duration("2h"));
+        assert_eq!(Some(Duration::from_secs(3 * 24 * 60 * 60)), parse_duration("3"));
+        assert_eq!(None, parse_duration("1y"));
+        assert_eq!(None, parse_duration("h"));
+    }
+
+    #[test]
+    fn parse_duration_overflowing() {
+        assert_eq!(None, parse_duration("18446744073709551615w"));
+    }
+}
@@ -0,0 +1,51 @@
+use super::CommandParser;
+use api::commands;
+use api::commands::{Command, Context, Report};
+use clap::{ArgMatches, Shell};
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub struct Completions {
+    pub shell: Shell,
+}
+
+impl Command for Completions {
+    fn execute(&self, ctx: &dyn Context) -> commands::Result<Report> {
+        let yml = load_yaml!("argv.yml");
+        let mut app = clap::App::from_yaml(yml);
+        app.gen_completions_to(ctx.get_appinfo().name, self.shell, &mut std::io::stdout());
+        Ok(Report::new())
+    }
+}
+
+impl CommandParser for Completions {
+    fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
+        if let ("completions", Some(matches)) = matches.subcommand() {
+            let shell = Shell::from_str(matches.value_of("shell")?).ok()?;
+            Some(commands::Execution::Run(Box::new(Completions { shell })))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_completions() {
+        for shell in &Shell::variants() {
+            let yml = load_yaml!("argv.yml");
+            let mut script = vec![];
+            clap::App::from_yaml(yml).gen_completions_to(
+                "markemptydirs",
+                Shell::from_str(shell).unwrap(),
+                &mut script,
+            );
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains("materialize"), "{}", shell);
+            assert!(script.contains("paths-from"), "{}", shell);
+        }
+    }
+}
@@ -1,39 +1,350 @@
+use super::environment as env;
 use super::ConfigParser;
 use api::commands;
+use api::fs;
+use api::fs::{Emptiness, MarkerStrategy, TransientError, TrashLocation};
 use api::notification::{LogLevel, MessageLength};
+use chrono::DateTime;
 use clap::ArgMatches;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 impl ConfigParser for commands::Config {
     fn parse(matches: &ArgMatches) -> Option<commands::Config> {
         let mut cfg = commands::Config::new();
 
-        if let Some(exclude_dirs) = matches.values_of("exclude-dirs") {
+        if let Some(exclude_dirs) =
+            env::values_of(matches, "exclude-dirs", "MARKEMPTYDIRS_EXCLUDE", ':')
+        {
             cfg.exclude_dirs = exclude_dirs.into_iter().map(PathBuf::from).collect();
         }
 
-        if let Some(marker_name) = matches.value_of("marker-name") {
-            cfg.marker_name = marker_name.to_owned();
+        if let Some(jobs) = env::value_of(matches, "jobs", "MARKEMPTYDIRS_JOBS") {
+            match jobs.parse::<usize>() {
+                Ok(jobs) if jobs > 0 => cfg.jobs = Some(jobs),
+                _ => {
+                    eprintln!("Invalid number of jobs '{}'", jobs);
+                    return None;
+                }
+            }
         }
 
-        cfg.log_level = match matches.occurrences_of("verbose") {
-            0 => LogLevel::Error,
-            1 => LogLevel::Warn,
-            2 => LogLevel::Info,
-            3 => LogLevel::Debug,
-            _ => LogLevel::Trace,
+        if let Some(lock_timeout) =
+            env::value_of(matches, "lock-timeout", "MARKEMPTYDIRS_LOCK_TIMEOUT")
+        {
+            match lock_timeout.parse::<u64>() {
+                Ok(seconds) => cfg.lock_timeout = Duration::from_secs(seconds),
+                Err(_) => {
+                    eprintln!("Invalid lock timeout '{}'", lock_timeout);
+                    return None;
+                }
+            }
+        }
+
+        cfg.lock_root_dirs = !env::is_present(matches, "no-lock", "MARKEMPTYDIRS_NO_LOCK");
+
+        if let Some(max_ops_per_sec) =
+            env::value_of(matches, "max-ops-per-sec", "MARKEMPTYDIRS_MAX_OPS_PER_SEC")
+        {
+            match max_ops_per_sec.parse::<u32>() {
+                Ok(max_ops_per_sec) if max_ops_per_sec > 0 => {
+                    cfg.throttle_policy.max_ops_per_sec = Some(max_ops_per_sec)
+                }
+                _ => {
+                    eprintln!(
+                        "Invalid number of operations per second '{}'",
+                        max_ops_per_sec
+                    );
+                    return None;
+                }
+            }
+        }
+
+        if let Some(max_concurrent_ops) = env::value_of(
+            matches,
+            "max-concurrent-ops",
+            "MARKEMPTYDIRS_MAX_CONCURRENT_OPS",
+        ) {
+            match max_concurrent_ops.parse::<usize>() {
+                Ok(max_concurrent_ops) if max_concurrent_ops > 0 => {
+                    cfg.throttle_policy.max_concurrent_ops = Some(max_concurrent_ops)
+                }
+                _ => {
+                    eprintln!(
+                        "Invalid number of concurrent operations '{}'",
+                        max_concurrent_ops
+                    );
+                    return None;
+                }
+            }
+        }
+
+        cfg.low_io_priority =
+            env::is_present(matches, "low-io-priority", "MARKEMPTYDIRS_LOW_IO_PRIORITY");
+
+        if let Some(retries) = env::value_of(matches, "retries", "MARKEMPTYDIRS_RETRIES") {
+            match retries.parse::<u32>() {
+                Ok(retries) => cfg.retry_policy.max_retries = retries,
+                Err(_) => {
+                    eprintln!("Invalid number of retries '{}'", retries);
+                    return None;
+                }
+            }
+        }
+
+        if let Some(retry_delay) =
+            env::value_of(matches, "retry-delay", "MARKEMPTYDIRS_RETRY_DELAY")
+        {
+            match retry_delay.parse::<u64>() {
+                Ok(millis) => cfg.retry_policy.initial_delay = Duration::from_millis(millis),
+                Err(_) => {
+                    eprintln!("Invalid retry delay '{}'", retry_delay);
+                    return None;
+                }
+            }
+        }
+
+        if let Some(retry_on) = env::values_of(matches, "retry-on", "MARKEMPTYDIRS_RETRY_ON", ',') {
+            let mut retried_errors = vec![];
+            for name in retry_on {
+                match parse_transient_error(&name) {
+                    Some(error) => retried_errors.push(error),
+                    None => {
+                        eprintln!("Invalid transient error '{}'", name);
+                        return None;
+                    }
+                }
+            }
+            cfg.retry_policy.retried_errors = retried_errors;
+        }
+
+        cfg.json_report = env::is_present(matches, "json", "MARKEMPTYDIRS_JSON");
+
+        if let Some(marker_name) =
+            env::value_of(matches, "marker-name", "MARKEMPTYDIRS_MARKER_NAME")
+        {
+            cfg.marker_name = marker_name;
+        }
+
+        cfg.marker_strategy =
+            match env::value_of(matches, "marker-strategy", "MARKEMPTYDIRS_STRATEGY") {
+                Some(ref strategy) if strategy == "manifest" => MarkerStrategy::Manifest,
+                Some(ref strategy) if strategy == "file" => MarkerStrategy::File,
+                Some(ref strategy) if strategy == "xattr" => MarkerStrategy::Xattr,
+                Some(strategy) => {
+                    eprintln!("Invalid marker strategy '{}'", strategy);
+                    return None;
+                }
+                None => MarkerStrategy::File,
+            };
+
+        if let Some(manifest_name) =
+            env::value_of(matches, "manifest-name", "MARKEMPTYDIRS_MANIFEST_NAME")
+        {
+            cfg.manifest_name = manifest_name;
+        }
+
+        if let Some(marker_mode) =
+            env::value_of(matches, "marker-mode", "MARKEMPTYDIRS_MARKER_MODE")
+        {
+            match u32::from_str_radix(&marker_mode, 8) {
+                Ok(mode) if mode <= 0o7777 => cfg.marker_mode = Some(mode),
+                _ => {
+                    eprintln!("Invalid marker mode '{}'", marker_mode);
+                    return None;
+                }
+            }
+        }
+
+        cfg.inherit_marker_owner =
+            env::is_present(matches, "inherit-owner", "MARKEMPTYDIRS_INHERIT_OWNER");
+
+        cfg.sign_markers = !env::is_present(matches, "no-sign", "MARKEMPTYDIRS_NO_SIGN");
+
+        if let Some(marker_mtime) =
+            env::value_of(matches, "marker-mtime", "MARKEMPTYDIRS_MARKER_MTIME")
+        {
+            match parse_time(&marker_mtime) {
+                Some(mtime) => cfg.marker_mtime = Some(mtime),
+                None => {
+                    eprintln!("Invalid marker modification time '{}'", marker_mtime);
+                    return None;
+                }
+            }
+        }
+
+        cfg.preserve_dir_times = env::is_present(
+            matches,
+            "preserve-dir-times",
+            "MARKEMPTYDIRS_PRESERVE_DIR_TIMES",
+        );
+
+        cfg.reproducible = env::is_present(matches, "reproducible", "MARKEMPTYDIRS_REPRODUCIBLE");
+        if cfg.reproducible {
+            if let Ok(source_date_epoch) = std::env::var("SOURCE_DATE_EPOCH") {
+                match source_date_epoch.parse::<u64>() {
+                    Ok(seconds) => {
+                        let date = UNIX_EPOCH + Duration::from_secs(seconds);
+                        cfg.source_date_epoch = Some(date);
+                        cfg.marker_mtime = cfg.marker_mtime.or(Some(date));
+                    }
+                    Err(_) => {
+                        eprintln!("Invalid SOURCE_DATE_EPOCH '{}'", source_date_epoch);
+                        return None;
+                    }
+                }
+            }
+        }
+
+        cfg.log_level = match env::occurrences_of(matches, "verbose", "MARKEMPTYDIRS_VERBOSE") {
+            Some(0) => LogLevel::Error,
+            Some(1) => LogLevel::Warn,
+            Some(2) => LogLevel::Info,
+            Some(3) => LogLevel::Debug,
+            Some(_) => LogLevel::Trace,
+            None => {
+                eprintln!("Invalid verbosity level in MARKEMPTYDIRS_VERBOSE");
+                return None;
+            }
         };
 
-        cfg.dereference_symlinks = matches.is_present("dereference-symlinks");
+        cfg.dereference_symlinks = env::is_present(
+            matches,
+            "dereference-symlinks",
+            "MARKEMPTYDIRS_DEREF_SYMLINKS",
+        );
+
+        // Not read from the environment, so that it is never given by accident.
+        cfg.force = matches.is_present("force");
 
-        cfg.message_length = if matches.is_present("short-messages") {
-            MessageLength::Short
-        } else {
-            MessageLength::Long
+        cfg.emptiness = match env::value_of(matches, "emptiness", "MARKEMPTYDIRS_EMPTINESS") {
+            Some(ref emptiness) if emptiness == "git-index" => Emptiness::GitIndex,
+            Some(ref emptiness) if emptiness == "git-head" => Emptiness::GitHead,
+            Some(ref emptiness) if emptiness == "filesystem" => Emptiness::FileSystem,
+            Some(emptiness) => {
+                eprintln!("Invalid emptiness mode '{}'", emptiness);
+                return None;
+            }
+            None => Emptiness::FileSystem,
         };
 
+        // The file may also be given by its variable, so clap cannot check this dependency.
+        let paths_from = env::value_of(matches, "paths-from", "MARKEMPTYDIRS_PATHS_FROM");
+        if paths_from.is_none() {
+            for &(name, option) in &[
+                ("null-separated", "--null"),
+                ("with-parents", "--with-parents"),
+            ] {
+                if matches.is_present(name) {
+                    eprintln!("Option {} requires --paths-from", option);
+                    return None;
+                }
+            }
+        }
+
+        if let Some(paths_from) = paths_from {
+            let null_separated = env::is_present(matches, "null-separated", "MARKEMPTYDIRS_NULL");
+            match fs::read_path_list(Path::new(&paths_from), null_separated) {
+                Ok(paths) => cfg.selected_paths = Some(paths),
+                Err(error) => {
+                    eprintln!("Cannot read paths from '{}': {}", paths_from, error);
+                    return None;
+                }
+            }
+        }
+
+        cfg.select_parents = env::is_present(matches, "with-parents", "MARKEMPTYDIRS_WITH_PARENTS");
+
+        // A trash option on the command line overrides both trash variables.
+        if matches.is_present("xdg-trash") {
+            cfg.trash = Some(TrashLocation::Xdg);
+        } else if let Some(trash_dir) = env::value_of(matches, "trash", "MARKEMPTYDIRS_TRASH") {
+            cfg.trash = Some(TrashLocation::Directory(PathBuf::from(trash_dir)));
+        } else if env::is_present(matches, "xdg-trash", "MARKEMPTYDIRS_XDG_TRASH") {
+            cfg.trash = Some(TrashLocation::Xdg);
+        }
+
+        cfg.message_length =
+            if env::is_present(matches, "short-messages", "MARKEMPTYDIRS_SHORT_MESSAGES") {
+                MessageLength::Short
+            } else {
+                MessageLength::Long
+            };
+
         cfg.executable_file = std::env::current_exe().unwrap();
 
         Some(cfg)
     }
 }
+
+fn parse_time(value: &str) -> Option<SystemTime> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(UNIX_EPOCH + Duration::from_secs(seconds));
+    }
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(SystemTime::from)
+}
+
+fn parse_transient_error(value: &str) -> Option<TransientError> {
+    match value.trim() {
+        "busy" => Some(TransientError::Busy),
+        "interrupted" => Some(TransientError::Interrupted),
+        "stale" => Some(TransientError::Stale),
+        "timed-out" => Some(TransientError::TimedOut),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::App;
+
+    fn parse(args: &[&str]) -> Option<commands::Config> {
+        let yml = load_yaml!("argv.yml");
+        let args = [&["markemptydirs"], args, &["list", "."]].concat();
+        commands::Config::parse(&App::from_yaml(yml).get_matches_from_safe(args).unwrap())
+    }
+
+    #[test]
+    fn parse_negated_flags() {
+        assert!(parse(&[]).unwrap().sign_markers);
+        assert!(!parse(&["--no-sign"]).unwrap().sign_markers);
+        assert!(parse(&["--no-sign", "--sign"]).unwrap().sign_markers);
+        assert!(!parse(&["--json", "--no-json"]).unwrap().json_report);
+    }
+
+    #[test]
+    fn parse_paths_from() {
+        let paths_file =
+            std::env::temp_dir().join(format!("markemptydirs_ui_paths_{}", std::process::id()));
+        std::fs::write(&paths_file, "a\0b").unwrap();
+        let paths_from = paths_file.to_str().unwrap();
+
+        assert!(parse(&["--null"]).is_none());
+        assert!(parse(&["--with-parents"]).is_none());
+        assert!(
+            !parse(&["--no-null", "--no-with-parents"])
+                .unwrap()
+                .select_parents
+        );
+
+        let cfg = parse(&["--paths-from", paths_from, "--null", "--with-parents"]).unwrap();
+        assert_eq!(
+            Some(vec![PathBuf::from("a"), PathBuf::from("b")]),
+            cfg.selected_paths
+        );
+        assert!(cfg.select_parents);
+
+        // The file given by the variable satisfies the options on the command line.
+        std::env::set_var("MARKEMPTYDIRS_PATHS_FROM", paths_from);
+        let cfg = parse(&["--null"]);
+        std::env::remove_var("MARKEMPTYDIRS_PATHS_FROM");
+        std::fs::remove_file(&paths_file).unwrap();
+        assert_eq!(
+            Some(vec![PathBuf::from("a"), PathBuf::from("b")]),
+            cfg.unwrap().selected_paths
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use clap::ArgMatches;
+use std::sync::Mutex;
+
+// Environment variables applied because their option was not given on the command line.
+static APPLIED_VARS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub fn take_applied_vars() -> Vec<String> {
+    std::mem::take(&mut *APPLIED_VARS.lock().unwrap())
+}
+
+// Options given on the command line take precedence over environment variables,
+// which in turn take precedence over default values.
+pub fn value_of(matches: &ArgMatches, name: &str, env_var: &str) -> Option<String> {
+    if matches.occurrences_of(name) == 0 {
+        if let Some(value) = get_var(env_var) {
+            record_var(env_var, &value);
+            return Some(value);
+        }
+    }
+    matches.value_of(name).map(|value| value.to_owned())
+}
+
+pub fn values_of(
+    matches: &ArgMatches,
+    name: &str,
+    env_var: &str,
+    delimiter: char,
+) -> Option<Vec<String>> {
+    if matches.occurrences_of(name) == 0 {
+        if let Some(value) = get_var(env_var) {
+            record_var(env_var, &value);
+            return Some(
+                value
+                    .split(delimiter)
+                    .map(|value| value.to_owned())
+                    .collect(),
+            );
+        }
+    }
+    matches
+        .values_of(name)
+        .map(|values| values.map(|value| value.to_owned()).collect())
+}
+
+// Flags are disabled again by their negation, like `--no-git` for `--git`. As both override
+// each other, clap only keeps the one given last, which takes precedence over the variable.
+pub fn is_present(matches: &ArgMatches, name: &str, env_var: &str) -> bool {
+    if matches.is_present(name) {
+        return true;
+    }
+    if matches.is_present(get_negation(name)) {
+        return false;
+    }
+    match get_var(env_var) {
+        Some(ref value) if ["1", "true", "yes", "on"].contains(&value.to_lowercase().as_str()) => {
+            record_var(env_var, value);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn get_negation(name: &str) -> String {
+    match name.strip_prefix("no-") {
+        Some(name) => name.to_owned(),
+        None => format!("no-{}", name),
+    }
+}
+
+// Returns None if the environment variable is not a number.
+pub fn occurrences_of(matches: &ArgMatches, name: &str, env_var: &str) -> Option<u64> {
+    match matches.occurrences_of(name) {
+        0 => match get_var(env_var) {
+            Some(value) => {
+                let occurrences = value.parse().ok();
+                if occurrences.is_some() {
+                    record_var(env_var, &value);
+                }
+                occurrences
+            }
+            None => Some(0),
+        },
+        occurrences => Some(occurrences),
+    }
+}
+
+fn get_var(name: &str) -> Option<String> {
+    match std::env::var(name) {
+        Ok(ref value) if value.is_empty() => None,
+        Ok(value) => Some(value),
+        Err(_) => None,
+    }
+}
+
+fn record_var(name: &str, value: &str) {
+    APPLIED_VARS
+        .lock()
+        .unwrap()
+        .push(format!("{}={}", name, value));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::App;
+    use std::env;
+
+    // Each test uses its own variables, as tests run in parallel.
+    fn with_matches<F: FnOnce(&ArgMatches)>(args: &[&str], f: F) {
+        let yml = load_yaml!("argv.yml");
+        let args = [&["markemptydirs"], args, &["list", "."]].concat();
+        f(&App::from_yaml(yml).get_matches_from_safe(args).unwrap());
+    }
+
+    #[test]
+    fn value_of_prefers_command_line() {
+        env::set_var("MARKEMPTYDIRS_TEST_JOBS", "2");
+        with_matches(&[], |matches| {
+            assert_eq!(
+                Some("2".to_owned()),
+                value_of(matches, "jobs", "MARKEMPTYDIRS_TEST_JOBS")
+            );
+        });
+        with_matches(&["--jobs", "3"], |matches| {
+            assert_eq!(
+                Some("3".to_owned()),
+                value_of(matches, "jobs", "MARKEMPTYDIRS_TEST_JOBS")
+            );
+        });
+        env::set_var("MARKEMPTYDIRS_TEST_JOBS", "");
+        with_matches(&[], |matches| {
+            assert_eq!(None, value_of(matches, "jobs", "MARKEMPTYDIRS_TEST_JOBS"));
+        });
+    }
+
+    #[test]
+    fn values_of_splits_variable() {
+        env::set_var("MARKEMPTYDIRS_TEST_EXCLUDE", "a:b");
+        with_matches(&[], |matches| {
+            assert_eq!(
+                Some(vec!["a".to_owned(), "b".to_owned()]),
+                values_of(matches, "exclude-dirs", "MARKEMPTYDIRS_TEST_EXCLUDE", ':')
+            );
+        });
+        with_matches(&["--exclude", "c"], |matches| {
+            assert_eq!(
+                Some(vec!["c".to_owned()]),
+                values_of(matches, "exclude-dirs", "MARKEMPTYDIRS_TEST_EXCLUDE", ':')
+            );
+        });
+    }
+
+    #[test]
+    fn is_present_reads_variable() {
+        for (value, expected) in &[
+            ("1", true),
+            ("Yes", true),
+            ("on", true),
+            ("0", false),
+            ("no", false),
+        ] {
+            env::set_var("MARKEMPTYDIRS_TEST_JSON", value);
+            with_matches(&[], |matches| {
+                assert_eq!(
+                    *expected,
+                    is_present(matches, "json", "MARKEMPTYDIRS_TEST_JSON")
+                );
+            });
+        }
+    }
+
+    #[test]
+    fn is_present_prefers_command_line() {
+        env::set_var("MARKEMPTYDIRS_TEST_SHORT", "1");
+        with_matches(&["--no-short-messages"], |matches| {
+            assert!(!is_present(
+                matches,
+                "short-messages",
+                "MARKEMPTYDIRS_TEST_SHORT"
+            ));
+        });
+        env::set_var("MARKEMPTYDIRS_TEST_SHORT", "0");
+        with_matches(&["--short-messages"], |matches| {
+            assert!(is_present(
+                matches,
+                "short-messages",
+                "MARKEMPTYDIRS_TEST_SHORT"
+            ));
+        });
+    }
+
+    #[test]
+    fn is_present_prefers_last_of_flag_and_negation() {
+        with_matches(&["--json", "--no-json"], |matches| {
+            assert!(!is_present(matches, "json", "MARKEMPTYDIRS_TEST_UNSET"));
+        });
+        with_matches(&["--no-json", "--json"], |matches| {
+            assert!(is_present(matches, "json", "MARKEMPTYDIRS_TEST_UNSET"));
+        });
+    }
+
+    #[test]
+    fn is_present_negates_inverted_flags() {
+        env::set_var("MARKEMPTYDIRS_TEST_NO_LOCK", "1");
+        with_matches(&[], |matches| {
+            assert!(is_present(matches, "no-lock", "MARKEMPTYDIRS_TEST_NO_LOCK"));
+        });
+        with_matches(&["--lock"], |matches| {
+            assert!(!is_present(
+                matches,
+                "no-lock",
+                "MARKEMPTYDIRS_TEST_NO_LOCK"
+            ));
+        });
+    }
+
+    #[test]
+    fn occurrences_of_rejects_non_numbers() {
+        env::set_var("MARKEMPTYDIRS_TEST_VERBOSE", "2");
+        with_matches(&[], |matches| {
+            assert_eq!(
+                Some(2),
+                occurrences_of(matches, "verbose", "MARKEMPTYDIRS_TEST_VERBOSE")
+            );
+        });
+        with_matches(&["-v"], |matches| {
+            assert_eq!(
+                Some(1),
+                occurrences_of(matches, "verbose", "MARKEMPTYDIRS_TEST_VERBOSE")
+            );
+        });
+        env::set_var("MARKEMPTYDIRS_TEST_VERBOSE", "many");
+        with_matches(&[], |matches| {
+            assert_eq!(
+                None,
+                occurrences_of(matches, "verbose", "MARKEMPTYDIRS_TEST_VERBOSE")
+            );
+        });
+    }
+}
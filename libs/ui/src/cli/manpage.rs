@@ -0,0 +1,236 @@
+use super::CommandParser;
+use api::commands;
+use api::commands::{Command, Context, Report};
+use clap::ArgMatches;
+use yaml_rust::Yaml;
+
+#[derive(PartialEq, Debug)]
+pub struct ManPage {}
+
+impl Command for ManPage {
+    fn execute(&self, _ctx: &dyn Context) -> commands::Result<Report> {
+        let yml = load_yaml!("argv.yml");
+        print!("{}", render_man_page(yml));
+        Ok(Report::new())
+    }
+}
+
+impl CommandParser for ManPage {
+    fn parse(matches: &ArgMatches) -> Option<commands::Execution> {
+        if let ("man", Some(_)) = matches.subcommand() {
+            Some(commands::Execution::Run(Box::new(ManPage {})))
+        } else {
+            None
+        }
+    }
+}
+
+// Renders the command line definition as roff document for man(1).
+pub fn render_man_page(yml: &Yaml) -> String {
+    let name = yml["name"].as_str().unwrap_or("");
+    let mut page = String::new();
+
+    page.push_str(&format!(
+        ".TH {} 1 \"\" \"{} {}\" \"User Commands\"\n",
+        name.to_uppercase(),
+        name,
+        yml["version"].as_str().unwrap_or("")
+    ));
+    page.push_str(".SH NAME\n");
+    page.push_str(&format!(
+        "{} \\- {}\n",
+        name,
+        escape(yml["about"].as_str().unwrap_or(""))
+    ));
+    page.push_str(".SH SYNOPSIS\n");
+    page.push_str(&format!("\\fB{}\\fR [OPTIONS] COMMAND\n", name));
+    page.push_str(".SH OPTIONS\n");
+    render_args(&mut page, &yml["args"]);
+    page.push_str(".SH COMMANDS\n");
+    render_subcommands(&mut page, name, &yml["subcommands"]);
+    if let Some(after_help) = yml["after_help"].as_str() {
+        render_sections(&mut page, after_help);
+    }
+    if let Some(author) = yml["author"].as_str() {
+        page.push_str(".SH AUTHOR\n");
+        page.push_str(&format!("{}\n", escape(author)));
+    }
+    page
+}
+
+fn render_subcommands(page: &mut String, parent: &str, subcommands: &Yaml) {
+    for (name, subcommand) in get_entries(subcommands) {
+        let command = format!("{} {}", parent, name);
+        let positionals: Vec<_> = get_entries(&subcommand["args"])
+            .into_iter()
+            .filter(|&(_, arg)| is_positional(arg))
+            .map(|(name, arg)| format_positional(name, arg))
+            .collect();
+        page.push_str(&format!(
+            ".SS \"{}{}\"\n",
+            escape(&command),
+            escape(
+                &positionals
+                    .iter()
+                    .map(|positional| format!(" {}", positional))
+                    .collect::<String>()
+            )
+        ));
+        if let Some(about) = subcommand["about"].as_str() {
+            render_text(page, about);
+        }
+        render_args(page, &subcommand["args"]);
+        render_subcommands(page, &command, &subcommand["subcommands"]);
+    }
+}
+
+fn render_args(page: &mut String, args: &Yaml) {
+    for (name, arg) in get_entries(args) {
+        let mut flags = vec![];
+        if let Some(short) = arg["short"].as_str() {
+            flags.push(format!("\\fB\\-{}\\fR", escape(short)));
+        }
+        if let Some(long) = arg["long"].as_str() {
+            flags.push(format!("\\fB\\-\\-{}\\fR", escape(long)));
+        }
+        let mut synopsis = if flags.is_empty() {
+            format!("\\fI{}\\fR", escape(&format_positional(name, arg)))
+        } else {
+            flags.join(", ")
+        };
+        if !flags.is_empty() && takes_value(arg) {
+            synopsis.push_str(&format!(" \\fI{}\\fR", escape(&get_value_name(name, arg))));
+        }
+
+        let mut help = arg["help"].as_str().unwrap_or("").to_owned();
+        if let Some(default_value) = arg["default_value"].as_str() {
+            help.push_str(&format!(" [default: {}]", default_value));
+        }
+        if let Some(possible_values) = arg["possible_values"].as_vec() {
+            let possible_values: Vec<_> = possible_values
+                .iter()
+                .filter_map(|value| value.as_str())
+                .collect();
+            help.push_str(&format!(
+                " [possible values: {}]",
+                possible_values.join(", ")
+            ));
+        }
+
+        page.push_str(".TP\n");
+        page.push_str(&format!("{}\n", synopsis));
+        page.push_str(&format!("{}\n", escape(&help)));
+    }
+}
+
+// Sections of the text start with an unindented heading like `EXAMPLES:`.
+fn render_sections(page: &mut String, text: &str) {
+    let mut sections = vec![("NOTES", vec![])];
+    for line in text.lines() {
+        if !line.starts_with(' ') && line.ends_with(':') {
+            sections.push((line.trim_end_matches(':'), vec![]));
+        } else if let Some(&mut (_, ref mut lines)) = sections.last_mut() {
+            lines.push(line.strip_prefix("    ").unwrap_or(line));
+        }
+    }
+    for (heading, mut lines) in sections {
+        while lines.last() == Some(&"") {
+            lines.pop();
+        }
+        if !lines.is_empty() {
+            page.push_str(&format!(".SH {}\n", escape(heading)));
+            render_text(page, &lines.join("\n"));
+        }
+    }
+}
+
+fn render_text(page: &mut String, text: &str) {
+    page.push_str(".PP\n.nf\n");
+    for line in text.lines() {
+        page.push_str(&format!("{}\n", escape(line)));
+    }
+    page.push_str(".fi\n");
+}
+
+fn get_entries(list: &Yaml) -> Vec<(&str, &Yaml)> {
+    list.as_vec()
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| entry.as_hash())
+                .flat_map(|hash| hash.iter())
+                .filter_map(|(name, value)| name.as_str().map(|name| (name, value)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn is_positional(arg: &Yaml) -> bool {
+    arg["short"].is_badvalue() && arg["long"].is_badvalue()
+}
+
+fn takes_value(arg: &Yaml) -> bool {
+    arg["takes_value"].as_bool().unwrap_or(false)
+        || !arg["value_name"].is_badvalue()
+        || !arg["default_value"].is_badvalue()
+}
+
+fn get_value_name(name: &str, arg: &Yaml) -> String {
+    arg["value_name"]
+        .as_str()
+        .map(|value_name| value_name.to_owned())
+        .unwrap_or_else(|| name.to_uppercase())
+}
+
+fn format_positional(name: &str, arg: &Yaml) -> String {
+    let value_name = get_value_name(name, arg);
+    let value_name = if arg["multiple"].as_bool().unwrap_or(false) {
+        format!("{}...", value_name)
+    } else {
+        value_name
+    };
+    if arg["required"].as_bool().unwrap_or(false) {
+        value_name
+    } else {
+        format!("[{}]", value_name)
+    }
+}
+
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\\\").replace('-', "\\-");
+    if text.starts_with('.') || text.starts_with('\'') {
+        format!("\\&{}", text)
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_man_page_sections() {
+        let page = render_man_page(load_yaml!("argv.yml"));
+        let headings: Vec<_> = page
+            .lines()
+            .filter(|line| line.starts_with(".SH "))
+            .collect();
+        assert_eq!(
+            vec![
+                ".SH NAME",
+                ".SH SYNOPSIS",
+                ".SH OPTIONS",
+                ".SH COMMANDS",
+                ".SH EXAMPLES",
+                ".SH DIRECTIVES",
+                ".SH ENVIRONMENT",
+                ".SH AUTHOR",
+            ],
+            headings
+        );
+        assert!(page.contains(".SS \"markemptydirs update [DIR...]\"\n"));
+        assert!(page.contains(".SH EXAMPLES\n.PP\n.nf\nmarkemptydirs update .\n"));
+        assert!(page.contains(".SH DIRECTIVES\n.PP\n.nf\nA marker whose text"));
+    }
+}
@@ -1,7 +1,15 @@
 mod command_parsing;
 
+mod completions;
+pub use self::completions::*;
+
 mod config_parsing;
 
+mod environment;
+
+mod manpage;
+pub use self::manpage::*;
+
 use api::application;
 use api::commands;
 use api::notification;
@@ -24,13 +32,19 @@ pub fn create_session(appinfo: application::ApplicationInfo) -> Option<applicati
         dbg!(&matches);
     }
 
-    if let Some(cfg) = commands::Config::parse(&matches) {
-        if let Some(exec) = commands::Command::parse(&matches) {
+    if let Some(mut cfg) = commands::Config::parse(&matches) {
+        if let Some(exec) = <dyn commands::Command>::parse(&matches) {
+            cfg.applied_env_vars = environment::take_applied_vars();
+            let notifier_factory = if cfg.json_report {
+                notification::StderrNotifier::create
+            } else {
+                notification::StdoutNotifier::create
+            };
             return Some(application::Session::new(
                 appinfo,
                 cfg,
                 exec,
-                notification::StdoutNotifier::create,
+                notifier_factory,
             ));
         }
     }
@@ -1,6 +1,9 @@
 extern crate api;
+extern crate chrono;
 
 #[macro_use]
 extern crate clap;
 
+extern crate yaml_rust;
+
 pub mod cli;
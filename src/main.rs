@@ -3,30 +3,34 @@ extern crate clap;
 extern crate std_logger;
 extern crate ui;
 
-use api::application::{ApplicationInfo, VersionInfo};
+use api::application::ApplicationInfo;
 use ui::cli::create_session;
 
 fn main() {
     std_logger::init();
 
-    let appinfo = create_appinfo();
-    let session = create_session(appinfo).unwrap();
+    let appinfo = ApplicationInfo::new();
+    let session = match create_session(appinfo) {
+        Some(session) => session,
+        None => std::process::exit(2),
+    };
 
     if cfg!(debug_assertions) {
         dbg!(&session);
     }
 
-    session.run().unwrap();
-}
-
-fn create_appinfo() -> ApplicationInfo {
-    ApplicationInfo {
-        copyright_year: 2018,
-        license: "Simplified BSD License",
-        name: "markemptydirs",
-        site: "https://github.com/jonnydee/markemptydirs-rs",
-        vendor_email: "jonny.dee@posteo.net",
-        vendor_name: "Johann Duscher",
-        version_info: VersionInfo::new_with_suffix(0, 1, 0, "beta1"),
+    match session.run() {
+        Ok(report) => {
+            if session.context.get_config().json_report {
+                println!("{}", report.to_json());
+            }
+            if !report.is_success() {
+                std::process::exit(1);
+            }
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
     }
 }